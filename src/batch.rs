@@ -0,0 +1,196 @@
+use create_gh_repo::error::{Error, Result};
+use create_gh_repo::git::GitMode;
+use create_gh_repo::forge::{self, Forge};
+use create_gh_repo::manifest::{CreateRequest, strip_comments};
+use create_gh_repo::secret::{self, Secret};
+use create_gh_repo::summary::Summary;
+use git_mode;
+
+use serde_json as json;
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::any::Any;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::channel;
+use std::thread;
+
+#[derive(Deserialize, Debug)]
+struct BatchFile {
+    #[serde(default)]
+    defaults: json::Map<String, json::Value>,
+    repositories: Vec<json::Map<String, json::Value>>,
+}
+
+struct BatchEntry {
+    request: CreateRequest,
+    mode: GitMode,
+    directory: Option<String>,
+}
+
+struct BatchResult {
+    name: String,
+    mode: GitMode,
+    result: Result<()>,
+    summary: Summary,
+}
+
+fn parse_mode(mode: &str) -> Result<GitMode> {
+    match mode {
+        "create" => Ok(GitMode::Create),
+        "clone" => Ok(GitMode::Clone),
+        "remote" => Ok(GitMode::Remote),
+        "push" => Ok(GitMode::Push),
+        _ => Err(Error::InvalidManifest(format!("mode \"{}\"", mode))),
+    }
+}
+
+fn mode_name(mode: GitMode) -> &'static str {
+    match mode {
+        GitMode::Create => "create",
+        GitMode::Clone => "clone",
+        GitMode::Remote => "remote",
+        GitMode::Push => "push",
+        GitMode::Init => "init",
+        GitMode::Rebase => "rebase",
+    }
+}
+
+fn load(path: &str) -> Result<Vec<BatchEntry>> {
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    let file: BatchFile = json::from_str(&strip_comments(&text)?)?;
+
+    let base = match json::to_value(CreateRequest::default())? {
+        json::Value::Object(base) => base,
+        _ => json::Map::new(),
+    };
+
+    let mut entries = Vec::new();
+    for repository in file.repositories {
+        let mut fields = base.clone();
+        for (key, value) in file.defaults.iter().chain(repository.iter()) {
+            fields.insert(key.clone(), value.clone());
+        }
+
+        let mode = match fields.remove("mode") {
+            Some(json::Value::String(mode)) => parse_mode(&mode)?,
+            Some(_) => return Err(Error::InvalidManifest("mode".into())),
+            None => GitMode::Create,
+        };
+        let directory = match fields.remove("directory") {
+            Some(json::Value::String(directory)) => Some(directory),
+            Some(json::Value::Null) | None => None,
+            Some(_) => return Err(Error::InvalidManifest("directory".into())),
+        };
+
+        entries.push(BatchEntry {
+            request: json::from_value(json::Value::Object(fields))?,
+            mode,
+            directory,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => {
+            payload.downcast_ref::<&str>().map_or("panicked".to_string(), |x| x.to_string())
+        }
+    }
+}
+
+fn run_entry(forge: &dyn Forge,
+             entry: &BatchEntry,
+             user: Option<&str>,
+             pass: Option<&Secret>,
+             summary: &mut Summary)
+             -> Result<()> {
+    let resolved = entry.request.resolve()?;
+    let res = forge::create(forge, &entry.request)?;
+    println!("Repository Created: {}", res.clone_url);
+
+    forge.apply_settings(&entry.request, &resolved, &res.full_name, None, summary);
+
+    let dir = entry.directory.as_deref();
+    git_mode(entry.mode, &res.clone_url, dir, user, pass)
+}
+
+pub fn run(forge: Arc<dyn Forge + Send + Sync>,
+           path: &str,
+           jobs: usize,
+           user: Option<String>,
+           pass: Option<Secret>)
+           -> Result<bool> {
+    let entries = load(path)?;
+    let count = entries.len();
+    let queue = Arc::new(Mutex::new(entries.into_iter().enumerate().collect::<VecDeque<_>>()));
+    let (tx, rx) = channel();
+
+    for _ in 0..jobs.max(1) {
+        let forge = forge.clone();
+        let queue = queue.clone();
+        let tx = tx.clone();
+        let user = user.clone();
+        let pass = pass.clone();
+        thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop_front();
+            let (index, entry) = match next {
+                Some(next) => next,
+                None => return,
+            };
+
+            let mut summary = Summary::new();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_entry(&*forge,
+                          &entry,
+                          user.as_deref(),
+                          pass.as_ref(),
+                          &mut summary)
+            }));
+            let result = result.unwrap_or_else(|payload| {
+                Err(Error::Panicked(panic_message(payload)))
+            });
+            let _ = tx.send((index,
+                             BatchResult {
+                                 name: entry.request.name.clone(),
+                                 mode: entry.mode,
+                                 result,
+                                 summary,
+                             }));
+        });
+    }
+    drop(tx);
+
+    let mut results: Vec<(usize, BatchResult)> = rx.iter().collect();
+    results.sort_by_key(|&(index, _)| index);
+
+    let width = results.iter().map(|(_, r)| r.name.len()).max().unwrap_or(0).max(10);
+    println!();
+    println!("{:width$}  {:6}  Status", "Repository", "Mode", width = width);
+    let mut success = results.len() == count;
+    for (_, r) in &results {
+        let status = match r.result {
+            Ok(()) if r.summary.is_success() => "ok".to_string(),
+            Ok(()) => format!("ok, {} steps failed", r.summary.failures()),
+            Err(ref e) => format!("failed: {}", secret::scrub(&e.to_string())),
+        };
+        success = success && r.result.is_ok() && r.summary.is_success();
+        println!("{:width$}  {:6}  {}", r.name, mode_name(r.mode), status, width = width);
+    }
+
+    for (_, r) in &results {
+        if !r.summary.is_success() {
+            println!();
+            println!("{}:", r.name);
+            r.summary.print();
+        }
+    }
+
+    Ok(success)
+}
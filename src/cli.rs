@@ -1,9 +1,16 @@
-use error::{Error, Result};
-use git;
-use git::GitMode;
+use create_gh_repo::credentials::{self, CredentialStore};
+use create_gh_repo::error::{Error, Result};
+use create_gh_repo::forge::ForgeKind;
+use create_gh_repo::git::{self, GitMode};
+use create_gh_repo::github_app::GitHubApp;
+use create_gh_repo::http::HttpOptions;
+use create_gh_repo::secret::Secret;
+use create_gh_repo::token_sources;
 
 use std::env;
-use clap::{Arg, ArgGroup, App, SubCommand, Shell};
+use std::time::Duration;
+use url::Url;
+use clap::{Arg, App, SubCommand, Shell};
 
 pub fn build_cli<'a>() -> App<'a, 'a> {
     App::new("Create GitHub Repositories")
@@ -25,23 +32,176 @@ pub fn build_cli<'a>() -> App<'a, 'a> {
             .short("p")
             .long("password")
             .requires("username")
+            .conflicts_with("token")
             .takes_value(true)
             .help("The password to your GitHub account"))
-        .group(ArgGroup::with_name("auth").args(&["token", "password"]))
+        .arg(Arg::with_name("account")
+            .long("account")
+            .takes_value(true)
+            .help("Uses the token stored for this account instead of the active one"))
+        .arg(Arg::with_name("otp")
+            .long("otp")
+            .takes_value(true)
+            .value_name("code")
+            .help("A two-factor authentication code for password authentication. You are \
+                   prompted for one when GitHub requires it"))
+        .arg(Arg::with_name("app-id")
+            .long("app-id")
+            .takes_value(true)
+            .requires_all(&["app-key", "installation-id"])
+            .conflicts_with_all(&["username", "token", "password", "account"])
+            .help("Authenticates as an installation of this GitHub App"))
+        .arg(Arg::with_name("app-key")
+            .long("app-key")
+            .takes_value(true)
+            .value_name("pem")
+            .requires("app-id")
+            .help("The private key of the GitHub App"))
+        .arg(Arg::with_name("installation-id")
+            .long("installation-id")
+            .takes_value(true)
+            .requires("app-id")
+            .help("The installation of the GitHub App to request an access token for"))
         .arg(Arg::with_name("editor")
             .short("e")
             .long("editor")
             .takes_value(true)
             .help("The command to run to edit the repository manifest"))
+        .arg(Arg::with_name("forge")
+            .long("forge")
+            .takes_value(true)
+            .possible_values(&["github", "gitlab", "gitea", "forgejo", "local"])
+            .default_value("github")
+            .help("The service to create the repository on"))
+        .arg(Arg::with_name("forge-url")
+            .long("forge-url")
+            .takes_value(true)
+            .value_name("url")
+            .help("The address of a self-hosted forge, for example https://gitlab.example.com. \
+                   The forge type is read from the create-gh-repo.<host>.forge git config \
+                   value when --forge is not given. A file:// address creates bare \
+                   repositories below that directory"))
+        .arg(Arg::with_name("timeout")
+            .long("timeout")
+            .takes_value(true)
+            .value_name("seconds")
+            .default_value("30")
+            .help("How long to wait for each API request before retrying it"))
+        .arg(Arg::with_name("retries")
+            .long("retries")
+            .takes_value(true)
+            .value_name("count")
+            .default_value("3")
+            .help("How often to retry API requests that time out, fail with a server error or \
+                   are rate limited"))
+        .arg(Arg::with_name("deploy-key")
+            .long("deploy-key")
+            .takes_value(true)
+            .value_name("path")
+            .help("Adds the public key at <path>.pub as a deploy key, generating an ed25519 \
+                   keypair at <path> if it does not exist"))
+        .arg(Arg::with_name("deploy-key-write")
+            .long("deploy-key-write")
+            .requires("deploy-key")
+            .help("Grants the deploy key write access to the repository"))
+        .arg(Arg::with_name("template")
+            .long("template")
+            .takes_value(true)
+            .value_name("owner/repo")
+            .help("Creates the repository from the given template repository"))
+        .arg(Arg::with_name("list-templates")
+            .long("list-templates")
+            .help("Lists your available template repositories in the repository manifest"))
+        .arg(Arg::with_name("local-templates")
+            .long("local-templates")
+            .help("In push mode, commits the README, license and .gitignore to the local \
+                   repository instead of having GitHub create them"))
+        .arg(Arg::with_name("rollback")
+            .long("rollback")
+            .help("Deletes the new repository again if a later step fails, which needs the \
+                   delete_repo scope"))
+        .arg(Arg::with_name("initial-branch")
+            .long("initial-branch")
+            .takes_value(true)
+            .value_name("name")
+            .help("The branch to create in init mode, read from the init.defaultBranch git \
+                   config value by default"))
         .arg(Arg::with_name("directory")
             .help("Sets an optional target directory for git operations")
             .index(2))
         .arg(Arg::with_name("mode")
             .index(1)
-            .possible_values(&["create", "clone", "remote", "push"])
+            .possible_values(&["create", "clone", "remote", "push", "init"])
             .default_value("clone")
             .required(true)
             .help("Action taken after creating github repository"))
+        .subcommand(SubCommand::with_name("fork")
+                    .arg(Arg::with_name("repository")
+                         .index(1)
+                         .value_name("owner/repo")
+                         .required(true)
+                         .help("The repository to fork"))
+                    .arg(Arg::with_name("directory")
+                         .index(2)
+                         .help("Sets an optional target directory for the clone"))
+                    .arg(Arg::with_name("org")
+                         .long("org")
+                         .takes_value(true)
+                         .help("Forks into this organization instead of your user account"))
+                    .arg(Arg::with_name("name")
+                         .long("name")
+                         .takes_value(true)
+                         .help("A new name for the fork"))
+                    .about("Fork a repository, clone it and add the original as 'upstream'"))
+        .subcommand(SubCommand::with_name("import")
+                    .arg(Arg::with_name("source")
+                         .index(1)
+                         .value_name("source-url")
+                         .required(true)
+                         .help("The repository to import, including all branches and tags"))
+                    .about("Create a repository and push the full history of another remote to it"))
+        .subcommand(SubCommand::with_name("batch")
+                    .arg(Arg::with_name("file")
+                         .index(1)
+                         .required(true)
+                         .help("A JSON file listing the repositories to create and their shared \
+                                defaults"))
+                    .arg(Arg::with_name("jobs")
+                         .short("j")
+                         .long("jobs")
+                         .takes_value(true)
+                         .default_value("4")
+                         .help("The number of repositories to create at the same time"))
+                    .about("Create several repositories from a manifest list file"))
+        .subcommand(SubCommand::with_name("login")
+                    .arg(Arg::with_name("client-id")
+                         .long("client-id")
+                         .takes_value(true)
+                         .help("The OAuth app to authorize, read from the \
+                                create-gh-repo.clientid git config value by default"))
+                    .arg(Arg::with_name("scopes")
+                         .long("scopes")
+                         .takes_value(true)
+                         .default_value("repo admin:repo_hook delete_repo")
+                         .help("The permissions to request for the token"))
+                    .about("Log in to GitHub in your browser and store the token for later runs"))
+        .subcommand(SubCommand::with_name("auth")
+                    .arg(Arg::with_name("action")
+                         .index(1)
+                         .possible_values(&["add", "list", "remove", "switch"])
+                         .required(true))
+                    .arg(Arg::with_name("account")
+                         .index(2)
+                         .required_ifs(&[("action", "add"), ("action", "remove"), ("action", "switch")])
+                         .help("The account to add, remove or make active"))
+                    .arg(Arg::with_name("host")
+                         .long("host")
+                         .takes_value(true)
+                         .default_value("github.com")
+                         .help("The host the account belongs to"))
+                    .about("Manage the tokens in the encrypted credential store"))
+        .subcommand(SubCommand::with_name("whoami")
+                    .about("Show the account, scopes and rate limit of the GitHub token"))
         .subcommand(SubCommand::with_name("completions") 
                     .arg(Arg::with_name("shell")
                          .index(1)
@@ -50,42 +210,142 @@ pub fn build_cli<'a>() -> App<'a, 'a> {
                     .about("Generate completion scripts for your shell"))
         .after_help("NOTES:{n}<username>, <token>, and <password> may alternatively be supplied \
                      by setting the GITHUB_USERNAME, GITHUB_TOKEN, or GITHUB_PASSWORD environment \
-                     variables")
+                     variables. Without GITHUB_TOKEN, the active account in the credential \
+                     store is used, see 'auth' and 'login', and then the token from ~/.netrc, \
+                     the gh CLI hosts file or 'git config github.token'. With --forge gitlab or \
+                     gitea, the token is read from GITLAB_TOKEN or GITEA_TOKEN, the credential \
+                     store and the forge host's entry in ~/.netrc instead")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Create,
+    Fork {
+        repository: String,
+        organization: Option<String>,
+        name: Option<String>,
+    },
+    Import { source: String },
+    Batch { file: String, jobs: usize },
+    Login {
+        client_id: Option<String>,
+        scopes: String,
+    },
+    Auth {
+        action: String,
+        host: String,
+        account: Option<String>,
+    },
+    Whoami,
 }
 
 pub struct CommandOptions {
+    pub command: Command,
+    pub forge: ForgeKind,
+    pub forge_url: Option<String>,
+    pub http: HttpOptions,
     pub editor: String,
-    pub auth: String,
+    pub auth: Secret,
     pub username: Option<String>,
-    pub password: Option<String>,
-    pub token: Option<String>,
+    pub password: Option<Secret>,
+    pub otp: Option<Secret>,
+    pub token: Option<Secret>,
+    pub app: Option<GitHubApp>,
     pub mode: GitMode,
+    pub initial_branch: String,
+    pub local_templates: bool,
+    pub rollback: bool,
     pub directory: Option<String>,
+    pub deploy_key: Option<String>,
+    pub deploy_key_write: bool,
+    pub template: Option<String>,
+    pub list_templates: bool,
 }
 
 pub struct CommandOptionsBuilder {
+    command: Command,
+    forge: ForgeKind,
+    forge_url: Option<String>,
+    http: HttpOptions,
+    github_token: Option<Secret>,
+    gitlab_token: Option<Secret>,
+    gitea_token: Option<Secret>,
     editor: Option<String>,
     username: Option<String>,
-    password: Option<String>,
-    token: Option<String>,
+    password: Option<Secret>,
+    account: Option<String>,
+    otp: Option<Secret>,
+    token: Option<Secret>,
+    app: Option<GitHubApp>,
     directory: Option<String>,
     mode: Option<GitMode>,
+    initial_branch: Option<String>,
+    local_templates: bool,
+    rollback: bool,
     token_auth: Option<bool>,
+    deploy_key: Option<String>,
+    deploy_key_write: bool,
+    template: Option<String>,
+    list_templates: bool,
 }
 
 impl CommandOptionsBuilder {
     pub fn new() -> CommandOptionsBuilder {
         CommandOptionsBuilder {
+            command: Command::Create,
+            forge: ForgeKind::GitHub,
+            forge_url: None,
+            http: HttpOptions::from_env(),
+            github_token: env::var("GITHUB_TOKEN").ok().map(Secret::new),
+            gitlab_token: env::var("GITLAB_TOKEN").ok().map(Secret::new),
+            gitea_token: env::var("GITEA_TOKEN").ok().map(Secret::new),
             editor: git::get_config_value("core.editor").or_else(|_| env::var("EDITOR")).ok(),
             username: env::var("GITHUB_USERNAME").ok(),
-            password: env::var("GITHUB_PASSWORD").ok(),
-            token: env::var("GITHUB_TOKEN").ok(),
+            password: env::var("GITHUB_PASSWORD").ok().map(Secret::new),
+            account: None,
+            otp: None,
+            token: None,
+            app: None,
             directory: None,
             mode: None,
+            initial_branch: None,
+            local_templates: false,
+            rollback: false,
             token_auth: None,
+            deploy_key: None,
+            deploy_key_write: false,
+            template: None,
+            list_templates: false,
         }
     }
 
+    pub fn command(&mut self, command: Command) -> &mut Self {
+        self.command = command;
+        self
+    }
+
+    pub fn forge(&mut self, forge: ForgeKind) -> &mut Self {
+        self.forge = forge;
+        self
+    }
+
+    pub fn forge_url<S>(&mut self, url: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.forge_url = Some(url.into());
+        self
+    }
+
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.http.timeout = timeout;
+        self
+    }
+
+    pub fn retries(&mut self, retries: u32) -> &mut Self {
+        self.http.retries = retries;
+        self
+    }
+
     pub fn editor<S>(&mut self, editor: S) -> &mut Self
         where S: Into<String>
     {
@@ -103,19 +363,38 @@ impl CommandOptionsBuilder {
     pub fn password<S>(&mut self, password: S) -> &mut Self
         where S: Into<String>
     {
-        self.password = Some(password.into());
+        self.password = Some(Secret::new(password));
         self.token_auth = Some(false);
         self
     }
 
+    pub fn account<S>(&mut self, account: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.account = Some(account.into());
+        self
+    }
+
+    pub fn otp<S>(&mut self, otp: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.otp = Some(Secret::new(otp));
+        self
+    }
+
     pub fn token<S>(&mut self, token: S) -> &mut Self
         where S: Into<String>
     {
-        self.token = Some(token.into());
+        self.token = Some(Secret::new(token));
         self.token_auth = Some(true);
         self
     }
 
+    pub fn app(&mut self, app: GitHubApp) -> &mut Self {
+        self.app = Some(app);
+        self
+    }
+
     pub fn directory<S>(&mut self, directory: S) -> &mut Self
         where S: Into<String>
     {
@@ -128,47 +407,184 @@ impl CommandOptionsBuilder {
         self
     }
 
-    pub fn build(self) -> Result<CommandOptions> {
+    pub fn initial_branch<S>(&mut self, branch: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.initial_branch = Some(branch.into());
+        self
+    }
+
+    pub fn local_templates(&mut self, local: bool) -> &mut Self {
+        self.local_templates = local;
+        self
+    }
+
+    pub fn rollback(&mut self, rollback: bool) -> &mut Self {
+        self.rollback = rollback;
+        self
+    }
+
+    pub fn deploy_key<S>(&mut self, path: S, write: bool) -> &mut Self
+        where S: Into<String>
+    {
+        self.deploy_key = Some(path.into());
+        self.deploy_key_write = write;
+        self
+    }
+
+    pub fn template<S>(&mut self, template: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.template = Some(template.into());
+        self
+    }
+
+    pub fn list_templates(&mut self, list: bool) -> &mut Self {
+        self.list_templates = list;
+        self
+    }
+
+    fn credential_host(&self) -> String {
+        let url = self.forge_url.as_ref().and_then(|url| Url::parse(url).ok());
+        match (self.forge, url.as_ref().and_then(|u| u.host_str())) {
+            (_, Some(host)) => host.into(),
+            (ForgeKind::GitHub, None) => "github.com".into(),
+            _ => "gitlab.com".into(),
+        }
+    }
+
+    fn needs_token(&self) -> bool {
+        match self.command {
+            Command::Login { .. } | Command::Auth { .. } => false,
+            _ => self.forge != ForgeKind::Local && self.token_auth.is_none() && self.app.is_none(),
+        }
+    }
+
+    fn stored_token(&self) -> Result<Option<Secret>> {
+        let missing = self.token.is_none() && self.password.is_none() && CredentialStore::exists();
+        if !self.needs_token() || !(self.account.is_some() || missing) {
+            return Ok(None);
+        }
+
+        let store = CredentialStore::open(credentials::passphrase()?)?;
+        let host = self.credential_host();
+        let account = self.account.as_deref();
+        match (store.token(&host, account), account) {
+            (Some(token), _) => {
+                info!("Using the token for {} from the credential store", host);
+                Ok(Some(token.clone()))
+            }
+            (None, Some(account)) => {
+                Err(Error::CredentialStore(format!("no account {} for {}", account, host)))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn build(mut self) -> Result<CommandOptions> {
+        let forge_token = match self.forge {
+            ForgeKind::GitHub => self.github_token.take(),
+            ForgeKind::GitLab => self.gitlab_token.take(),
+            ForgeKind::Gitea => self.gitea_token.take(),
+            ForgeKind::Local => None,
+        };
+        if self.token.is_none() {
+            self.token = forge_token;
+        }
+        // A token saved with login wins over the ones other tools keep
+        if let Some(token) = self.stored_token()? {
+            self.token = Some(token);
+            self.token_auth = Some(true);
+        }
+        if self.token.is_none() && self.account.is_none() && self.needs_token() {
+            self.token = token_sources::forge_token(self.forge, &self.credential_host());
+        }
+
         let auth = match self.token_auth {
             None => {
                 if self.token.is_some() {
                     self.token.clone()
                 } else if self.password.is_some() && self.username.is_some() {
-                    Some(format!("{}:{}",
-                                 self.username.as_ref().unwrap(),
-                                 self.password.as_ref().unwrap()))
+                    Some(Secret::new(format!("{}:{}",
+                                             self.username.as_ref().unwrap(),
+                                             self.password.as_ref().unwrap().expose())))
                 } else {
                     None
                 }
             }
             Some(true) => self.token.clone(),
             Some(false) => {
-                if self.password.is_some() && self.username.is_some() {
-                    Some(format!("{}:{}",
-                                 self.username.as_ref().unwrap(),
-                                 self.password.as_ref().unwrap()))
-                } else {
-                    None
+                match (self.username.as_ref(), self.password.as_ref()) {
+                    (Some(username), Some(password)) => {
+                        Some(Secret::new(format!("{}:{}", username, password.expose())))
+                    }
+                    _ => None,
                 }
             }
         };
 
-        let auth = try!(auth.ok_or(Error::MissingParameter("authentication".into())));
-        let editor = try!(self.editor.ok_or(Error::MissingParameter("editor".into())));
-        let mode = try!(self.mode.ok_or(Error::MissingParameter("mode".into())));
+        let password_auth = match self.token_auth {
+            Some(auth) => !auth,
+            None => self.token.is_none(),
+        };
+        let auth = match (self.forge, &self.command) {
+            (ForgeKind::Local, _) |
+            (_, &Command::Login { .. }) |
+            (_, &Command::Auth { .. }) => auth.unwrap_or_default(),
+            _ if self.app.is_some() => auth.unwrap_or_default(),
+            (ForgeKind::GitLab, _) |
+            (ForgeKind::Gitea, _) if password_auth && auth.is_some() => {
+                return Err(Error::Unsupported("password authentication, use a token".into()))
+            }
+            _ => auth.ok_or(Error::MissingParameter("authentication".into()))?,
+        };
+        let editor = match self.command {
+            Command::Create | Command::Import { .. } => {
+                self.editor.ok_or(Error::MissingParameter("editor".into()))?
+            }
+            _ => self.editor.unwrap_or_default(),
+        };
+        let mode = self.mode.ok_or(Error::MissingParameter("mode".into()))?;
+        if self.local_templates && (mode != GitMode::Push || self.command != Command::Create) {
+            return Err(Error::InvalidValue("--local-templates outside push mode".into()));
+        }
 
         Ok(CommandOptions {
-            editor: editor,
-            auth: auth,
+            command: self.command,
+            forge: self.forge,
+            forge_url: self.forge_url,
+            http: self.http,
+            editor,
+            auth,
             username: self.username,
             password: self.password,
+            otp: self.otp,
             directory: self.directory,
             token: self.token,
-            mode: mode,
+            app: self.app,
+            mode,
+            initial_branch: self.initial_branch
+                .or_else(|| git::get_config_value("init.defaultBranch").ok())
+                .unwrap_or_else(|| "master".into()),
+            local_templates: self.local_templates,
+            rollback: self.rollback,
+            deploy_key: self.deploy_key,
+            deploy_key_write: self.deploy_key_write,
+            template: self.template,
+            list_templates: self.list_templates,
         })
     }
 }
 
+fn get_host_forge(forge_url: &str) -> Option<String> {
+    let url = Url::parse(forge_url).ok();
+    if url.as_ref().is_some_and(|u| u.scheme() == "file") {
+        return Some("local".into());
+    }
+    let host = url.as_ref().and_then(|u| u.host_str());
+    host.and_then(|host| git::get_config_value(&format!("create-gh-repo.{}.forge", host)).ok())
+}
+
 pub fn get_options(args: Option<Vec<&str>>) -> Result<CommandOptions> {
     let matches = if let Some(args) = args {
         build_cli().get_matches_from(args)
@@ -189,17 +605,81 @@ pub fn get_options(args: Option<Vec<&str>>) -> Result<CommandOptions> {
         ::std::process::exit(1);
     }
 
+    let mut builder = CommandOptionsBuilder::new();
+
+    if let Some(matches) = matches.subcommand_matches("fork") {
+        builder.command(Command::Fork {
+            repository: matches.value_of("repository").unwrap_or("").into(),
+            organization: matches.value_of("org").map(|x| x.into()),
+            name: matches.value_of("name").map(|x| x.into()),
+        });
+        if let Some(directory) = matches.value_of("directory") {
+            builder.directory(directory);
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("import") {
+        builder.command(Command::Import { source: matches.value_of("source").unwrap_or("").into() });
+    }
+
+    if let Some(matches) = matches.subcommand_matches("batch") {
+        let jobs = matches.value_of("jobs").unwrap_or("4");
+        builder.command(Command::Batch {
+            file: matches.value_of("file").unwrap_or("").into(),
+            jobs: jobs.parse().map_err(|_| Error::InvalidValue(format!("--jobs {}", jobs)))?,
+        });
+    }
+
+    if let Some(matches) = matches.subcommand_matches("login") {
+        builder.command(Command::Login {
+            client_id: matches.value_of("client-id")
+                .map(|x| x.to_string())
+                .or_else(|| git::get_config_value("create-gh-repo.clientid").ok()),
+            scopes: matches.value_of("scopes").unwrap_or("").into(),
+        });
+    }
+
+    if let Some(matches) = matches.subcommand_matches("auth") {
+        builder.command(Command::Auth {
+            action: matches.value_of("action").unwrap_or("list").into(),
+            host: matches.value_of("host").unwrap_or("github.com").into(),
+            account: matches.value_of("account").map(|x| x.into()),
+        });
+    }
+
+    if matches.subcommand_matches("whoami").is_some() {
+        builder.command(Command::Whoami);
+    }
+
     let mode = match matches.value_of("mode") {
         Some("create") => GitMode::Create,
         Some("clone") => GitMode::Clone,
         Some("remote") => GitMode::Remote,
         Some("push") => GitMode::Push,
+        Some("init") => GitMode::Init,
         Some("rebase") => GitMode::Rebase,
         _ => GitMode::Clone,
     };
 
-    let mut builder = CommandOptionsBuilder::new();
-
+    let forge_url = matches.value_of("forge-url");
+    let forge = if matches.occurrences_of("forge") == 0 {
+        forge_url.and_then(get_host_forge).unwrap_or("github".into())
+    } else {
+        matches.value_of("forge").unwrap_or("github").into()
+    };
+    builder.forge(forge.parse()?);
+    if let Some(url) = forge_url {
+        builder.forge_url(url);
+    }
+    if let Some(timeout) = matches.value_of("timeout") {
+        let timeout = timeout.parse()
+            .map_err(|_| Error::InvalidValue(format!("--timeout {}", timeout)))?;
+        builder.timeout(Duration::from_secs(timeout));
+    }
+    if let Some(retries) = matches.value_of("retries") {
+        builder.retries(retries.parse()
+            .map_err(|_| Error::InvalidValue(format!("--retries {}", retries)))?);
+    }
     if let Some(editor) = matches.value_of("editor") {
         builder.editor(editor);
     }
@@ -209,12 +689,35 @@ pub fn get_options(args: Option<Vec<&str>>) -> Result<CommandOptions> {
     if let Some(password) = matches.value_of("password") {
         builder.password(password);
     }
+    if let Some(account) = matches.value_of("account") {
+        builder.account(account);
+    }
+    if let Some(otp) = matches.value_of("otp") {
+        builder.otp(otp);
+    }
     if let Some(token) = matches.value_of("token") {
         builder.token(token);
     }
+    if let Some(app_id) = matches.value_of("app-id") {
+        builder.app(GitHubApp::new(app_id,
+                                   matches.value_of("app-key").unwrap_or(""),
+                                   matches.value_of("installation-id").unwrap_or("")));
+    }
     if let Some(directory) = matches.value_of("directory") {
         builder.directory(directory);
     }
+    if let Some(branch) = matches.value_of("initial-branch") {
+        builder.initial_branch(branch);
+    }
+    if let Some(deploy_key) = matches.value_of("deploy-key") {
+        builder.deploy_key(deploy_key, matches.is_present("deploy-key-write"));
+    }
+    if let Some(template) = matches.value_of("template") {
+        builder.template(template);
+    }
+    builder.list_templates(matches.is_present("list-templates"));
+    builder.local_templates(matches.is_present("local-templates"));
+    builder.rollback(matches.is_present("rollback"));
     builder.mode(mode);
     builder.build()
 }
@@ -224,12 +727,12 @@ pub fn get_options(args: Option<Vec<&str>>) -> Result<CommandOptions> {
 mod tests {
     use super::*;
     use std::env;
-    use git::GitMode;
+    use create_gh_repo::git::GitMode;
 
     #[test]
     fn display_help() {
         build_cli().print_help().unwrap();
-        println!("");
+        println!();
     }
 
     fn clear_vars() {
@@ -237,6 +740,11 @@ mod tests {
         env::remove_var("GITHUB_USERNAME");
         env::remove_var("GITHUB_PASSWORD");
         env::remove_var("GITHUB_TOKEN");
+        env::remove_var("GITLAB_TOKEN");
+        env::remove_var("GITEA_TOKEN");
+        env::set_var("XDG_CONFIG_HOME", "/nonexistent");
+        env::set_var("GH_CONFIG_DIR", "/nonexistent");
+        env::set_var("HOME", "/nonexistent");
     }
 
     #[test]
@@ -250,8 +758,8 @@ mod tests {
 
         let opts = get_options(None).unwrap();
         assert_eq!(opts.username, Some("user".to_string()));
-        assert_eq!(opts.password, Some("pass".to_string()));
-        assert_eq!(opts.auth, "user:pass".to_string());
+        assert_eq!(opts.password, Some(Secret::new("pass")));
+        assert_eq!(opts.auth.expose(), "user:pass");
         assert_eq!(opts.mode, GitMode::Clone);
 
         clear_vars();
@@ -262,8 +770,8 @@ mod tests {
 
         let opts = get_options(None).unwrap();
         assert_eq!(opts.username, Some("user".to_string()));
-        assert_eq!(opts.password, Some("pass".to_string()));
-        assert_eq!(opts.auth, "token".to_string());
+        assert_eq!(opts.password, Some(Secret::new("pass")));
+        assert_eq!(opts.auth.expose(), "token");
         assert_eq!(opts.mode, GitMode::Clone);
     }
 
@@ -273,8 +781,8 @@ mod tests {
         let opts = vec!["create_gh_repo", "-e=vim", "-p=pass", "-u=user", "create", "somedir"];
         let opts = get_options(Some(opts)).unwrap();
         assert_eq!(opts.username, Some("user".to_string()));
-        assert_eq!(opts.password, Some("pass".to_string()));
-        assert_eq!(opts.auth, "user:pass".to_string());
+        assert_eq!(opts.password, Some(Secret::new("pass")));
+        assert_eq!(opts.auth.expose(), "user:pass");
         assert_eq!(opts.mode, GitMode::Create);
         assert_eq!(opts.editor, "vim".to_string());
         assert_eq!(opts.directory, Some("somedir".to_string()));
@@ -283,12 +791,14 @@ mod tests {
                         "--editor=vim",
                         "--password=pass",
                         "--user=user",
+                        "--otp=123456",
                         "create",
                         "somedir"];
         let opts = get_options(Some(opts)).unwrap();
         assert_eq!(opts.username, Some("user".to_string()));
-        assert_eq!(opts.password, Some("pass".to_string()));
-        assert_eq!(opts.auth, "user:pass".to_string());
+        assert_eq!(opts.password, Some(Secret::new("pass")));
+        assert_eq!(opts.otp, Some(Secret::new("123456")));
+        assert_eq!(opts.auth.expose(), "user:pass");
         assert_eq!(opts.mode, GitMode::Create);
         assert_eq!(opts.editor, "vim".to_string());
         assert_eq!(opts.directory, Some("somedir".to_string()));
@@ -297,9 +807,225 @@ mod tests {
         let opts = get_options(Some(opts)).unwrap();
         assert_eq!(opts.username, None);
         assert_eq!(opts.password, None);
-        assert_eq!(opts.auth, "token".to_string());
+        assert_eq!(opts.auth.expose(), "token");
         assert_eq!(opts.mode, GitMode::Create);
         assert_eq!(opts.editor, "vim".to_string());
         assert_eq!(opts.directory, Some("somedir".to_string()));
+
+        let opts = vec!["create_gh_repo",
+                        "--editor=vim",
+                        "--token=token",
+                        "--initial-branch=main",
+                        "init",
+                        "somedir"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.mode, GitMode::Init);
+        assert_eq!(opts.initial_branch, "main".to_string());
+        assert_eq!(opts.directory, Some("somedir".to_string()));
+
+        let opts = vec!["create_gh_repo",
+                        "--editor=vim",
+                        "--token=token",
+                        "--local-templates",
+                        "--rollback",
+                        "push"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.mode, GitMode::Push);
+        assert!(opts.local_templates);
+        assert!(opts.rollback);
+
+        for mode in &["clone", "remote", "create", "init"] {
+            let opts = vec!["create_gh_repo", "-e=vim", "-t=token", "--local-templates", mode];
+            match get_options(Some(opts)) {
+                Err(Error::InvalidValue(_)) => {}
+                _ => panic!("expected --local-templates to be rejected in {} mode", mode),
+            }
+        }
+    }
+
+    #[test]
+    fn set_forge() {
+        clear_vars();
+        let opts = vec!["create_gh_repo", "-e=vim", "-t=token"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.forge, ForgeKind::GitHub);
+        assert_eq!(opts.forge_url, None);
+        assert_eq!(opts.http.timeout, HttpOptions::default().timeout);
+
+        let opts = vec!["create_gh_repo",
+                        "-e=vim",
+                        "-t=token",
+                        "--forge=gitlab",
+                        "--forge-url=https://gitlab.example.com",
+                        "--timeout=10",
+                        "--retries=0"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.http.timeout, Duration::from_secs(10));
+        assert_eq!(opts.http.retries, 0);
+        assert_eq!(opts.forge, ForgeKind::GitLab);
+        assert_eq!(opts.forge_url, Some("https://gitlab.example.com".to_string()));
+        assert_eq!(opts.auth.expose(), "token");
+
+        env::set_var("GITEA_TOKEN", "gitea");
+        let opts = vec!["create_gh_repo",
+                        "-e=vim",
+                        "--forge=forgejo",
+                        "--forge-url=https://code.example.com"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.forge, ForgeKind::Gitea);
+        assert_eq!(opts.auth.expose(), "gitea");
+        clear_vars();
+
+        let opts = vec!["create_gh_repo", "-e=vim", "--forge-url=file:///srv/git"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.forge, ForgeKind::Local);
+        assert_eq!(opts.auth.expose(), "");
+
+        let opts = vec!["create_gh_repo", "-e=vim", "-u=user", "-p=pass", "--forge=gitlab"];
+        match get_options(Some(opts)) {
+            Err(Error::Unsupported(_)) => {}
+            _ => panic!("expected password authentication to be rejected for GitLab"),
+        }
+    }
+
+    #[test]
+    fn forge_tokens_stay_with_their_forge() {
+        clear_vars();
+        let mut builder = CommandOptionsBuilder::new();
+        builder.github_token = Some(Secret::new("github"));
+        builder.gitlab_token = None;
+        builder.forge(ForgeKind::GitLab)
+            .forge_url("https://gitlab.example.com")
+            .editor("vim")
+            .mode(GitMode::Create);
+        match builder.build() {
+            Err(Error::MissingParameter(ref name)) if name == "authentication" => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(opts) => panic!("used {:?} for GitLab", opts.token),
+        }
+
+        let mut builder = CommandOptionsBuilder::new();
+        builder.github_token = Some(Secret::new("github"));
+        builder.gitlab_token = Some(Secret::new("gitlab"));
+        builder.editor("vim").mode(GitMode::Create);
+        let opts = builder.build().unwrap();
+        assert_eq!(opts.auth.expose(), "github");
+    }
+
+    #[test]
+    fn set_deploy_key() {
+        clear_vars();
+        let opts = vec!["create_gh_repo", "-e=vim", "-t=token", "--deploy-key=keys/deploy"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.deploy_key, Some("keys/deploy".to_string()));
+        assert!(!opts.deploy_key_write);
+
+        let opts = vec!["create_gh_repo",
+                        "-e=vim",
+                        "-t=token",
+                        "--deploy-key=keys/deploy",
+                        "--deploy-key-write"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.deploy_key, Some("keys/deploy".to_string()));
+        assert!(opts.deploy_key_write);
+    }
+
+    #[test]
+    fn set_fork() {
+        clear_vars();
+        let opts = vec!["create_gh_repo", "-t=token", "fork", "rust-lang/rust", "somedir"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command,
+                   Command::Fork {
+                       repository: "rust-lang/rust".to_string(),
+                       organization: None,
+                       name: None,
+                   });
+        assert_eq!(opts.directory, Some("somedir".to_string()));
+
+        let opts = vec!["create_gh_repo",
+                        "-t=token",
+                        "fork",
+                        "--org=myorg",
+                        "--name=rusty",
+                        "rust-lang/rust"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command,
+                   Command::Fork {
+                       repository: "rust-lang/rust".to_string(),
+                       organization: Some("myorg".to_string()),
+                       name: Some("rusty".to_string()),
+                   });
+        assert_eq!(opts.directory, None);
+    }
+
+    #[test]
+    fn set_import() {
+        clear_vars();
+        let opts = vec!["create_gh_repo",
+                        "-e=vim",
+                        "-t=token",
+                        "import",
+                        "https://git.example.com/team/project.git"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command,
+                   Command::Import { source: "https://git.example.com/team/project.git".to_string() });
+    }
+
+    #[test]
+    fn set_batch() {
+        clear_vars();
+        let opts = vec!["create_gh_repo", "-t=token", "batch", "repos.json"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command,
+                   Command::Batch {
+                       file: "repos.json".to_string(),
+                       jobs: 4,
+                   });
+
+        let opts = vec!["create_gh_repo", "-t=token", "batch", "-j=2", "repos.json"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command,
+                   Command::Batch {
+                       file: "repos.json".to_string(),
+                       jobs: 2,
+                   });
+
+        let opts = vec!["create_gh_repo", "-t=token", "batch", "-j=many", "repos.json"];
+        match get_options(Some(opts)) {
+            Err(Error::InvalidValue(ref value)) if value == "--jobs many" => {}
+            _ => panic!("expected an invalid --jobs value"),
+        }
+    }
+
+    #[test]
+    fn set_login() {
+        clear_vars();
+        let opts = vec!["create_gh_repo", "login", "--client-id=abc123"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command,
+                   Command::Login {
+                       client_id: Some("abc123".to_string()),
+                       scopes: "repo admin:repo_hook delete_repo".to_string(),
+                   });
+        assert_eq!(opts.auth.expose(), "");
+
+        let opts = vec!["create_gh_repo", "auth", "switch", "work", "--host=gitlab.com"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command,
+                   Command::Auth {
+                       action: "switch".to_string(),
+                       host: "gitlab.com".to_string(),
+                       account: Some("work".to_string()),
+                   });
+    }
+
+    #[test]
+    fn set_whoami() {
+        clear_vars();
+        let opts = vec!["create_gh_repo", "-t=token", "whoami"];
+        let opts = get_options(Some(opts)).unwrap();
+        assert_eq!(opts.command, Command::Whoami);
+        assert_eq!(opts.auth.expose(), "token");
     }
 }
@@ -0,0 +1,266 @@
+extern crate rpassword;
+
+use error::{Error, Result};
+use secret::Secret;
+
+use atty;
+use base64;
+use serde_json as json;
+use sodiumoxide::crypto::pwhash;
+use sodiumoxide::crypto::secretbox;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct StoreFile {
+    salt: String,
+    nonce: String,
+    data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct HostAccounts {
+    active: Option<String>,
+    #[serde(default)]
+    accounts: BTreeMap<String, Secret>,
+}
+
+pub struct CredentialStore {
+    hosts: Hosts,
+    passphrase: Secret,
+}
+
+fn store_path() -> Result<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".config")));
+    config.map(|dir| dir.join("create-gh-repo").join("credentials.json"))
+        .ok_or(Error::MissingParameter("home directory".into()))
+}
+
+#[cfg(unix)]
+fn create_private(path: &Path) -> Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| e.into())
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> Result<File> {
+    File::create(path).map_err(|e| e.into())
+}
+
+fn derive_key(passphrase: &str, salt: &pwhash::Salt) -> Result<secretbox::Key> {
+    let mut key = secretbox::Key([0; secretbox::KEYBYTES]);
+    {
+        let secretbox::Key(ref mut bytes) = key;
+        pwhash::derive_key(bytes,
+                                passphrase.as_bytes(),
+                                salt,
+                                pwhash::OPSLIMIT_INTERACTIVE,
+                                pwhash::MEMLIMIT_INTERACTIVE)
+            .map_err(|_| Error::CredentialStore("could not derive key".into()))?;
+    }
+    Ok(key)
+}
+
+pub fn read_secret(prompt: &str) -> Result<Secret> {
+    print!("{}: ", prompt);
+    io::stdout().flush()?;
+    rpassword::read_password().map(Secret::new).map_err(|e| e.into())
+}
+
+pub fn passphrase() -> Result<Secret> {
+    match env::var("CREATE_GH_REPO_PASSPHRASE") {
+        Ok(passphrase) => Ok(Secret::new(passphrase)),
+        Err(_) if !atty::is(atty::Stream::Stdin) => {
+            Err(Error::CredentialStore("set CREATE_GH_REPO_PASSPHRASE to unlock the store when \
+                                        stdin is not a terminal"
+                .into()))
+        }
+        Err(_) => read_secret("Credential store passphrase"),
+    }
+}
+
+type Hosts = BTreeMap<String, HostAccounts>;
+
+fn seal(hosts: &Hosts, passphrase: &Secret) -> Result<StoreFile> {
+    let salt = pwhash::gen_salt();
+    let nonce = secretbox::gen_nonce();
+    let key = derive_key(passphrase.expose(), &salt)?;
+    let data = secretbox::seal(&json::to_vec(hosts)?, &nonce, &key);
+    Ok(StoreFile {
+        salt: base64::encode(&salt[..]),
+        nonce: base64::encode(&nonce[..]),
+        data: base64::encode(&*data),
+    })
+}
+
+fn unseal(file: &StoreFile, passphrase: &Secret) -> Result<Hosts> {
+    let salt = pwhash::Salt::from_slice(&base64::decode(&*file.salt)?)
+        .ok_or(Error::CredentialStore("invalid salt".into()))?;
+    let nonce = secretbox::Nonce::from_slice(&base64::decode(&*file.nonce)?)
+        .ok_or(Error::CredentialStore("invalid nonce".into()))?;
+    let key = derive_key(passphrase.expose(), &salt)?;
+    let data = secretbox::open(&base64::decode(&*file.data)?, &nonce, &key)
+        .map_err(|_| Error::CredentialStore("incorrect passphrase".into()))?;
+    json::from_slice(&data).map_err(|e| e.into())
+}
+
+impl CredentialStore {
+    pub fn exists() -> bool {
+        store_path().map(|path| path.exists()).unwrap_or(false)
+    }
+
+    pub fn open<S>(passphrase: S) -> Result<CredentialStore>
+        where S: Into<Secret>
+    {
+        let mut store = CredentialStore {
+            hosts: BTreeMap::new(),
+            passphrase: passphrase.into(),
+        };
+
+        let mut text = String::new();
+        match File::open(store_path()?) {
+            Ok(mut file) => file.read_to_string(&mut text)?,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(store),
+            Err(e) => return Err(e.into()),
+        };
+        let file: StoreFile = json::from_str(&text)?;
+        store.hosts = unseal(&file, &store.passphrase)?;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = store_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let file = seal(&self.hosts, &self.passphrase)?;
+        let mut out = create_private(&path)?;
+        out.write_all(&json::to_vec_pretty(&file)?)?;
+        Ok(())
+    }
+
+    pub fn token(&self, host: &str, account: Option<&str>) -> Option<&Secret> {
+        self.hosts.get(host).and_then(|h| {
+            account.or(h.active.as_deref()).and_then(|account| h.accounts.get(account))
+        })
+    }
+
+    pub fn add<S>(&mut self, host: &str, account: &str, token: S)
+        where S: Into<Secret>
+    {
+        let accounts = self.hosts.entry(host.to_string()).or_default();
+        accounts.accounts.insert(account.to_string(), token.into());
+        if accounts.active.is_none() {
+            accounts.active = Some(account.to_string());
+        }
+    }
+
+    pub fn remove(&mut self, host: &str, account: &str) -> Result<()> {
+        let empty = {
+            let accounts = self.hosts
+                .get_mut(host)
+                .ok_or(Error::CredentialStore(format!("no accounts for {}", host)))?;
+            accounts.accounts
+                .remove(account)
+                .ok_or(Error::CredentialStore(format!("no account {} for {}", account, host)))?;
+            if accounts.active.as_deref() == Some(account) {
+                accounts.active = accounts.accounts.keys().next().cloned();
+            }
+            accounts.accounts.is_empty()
+        };
+        if empty {
+            self.hosts.remove(host);
+        }
+        Ok(())
+    }
+
+    pub fn switch(&mut self, host: &str, account: &str) -> Result<()> {
+        let accounts = self.hosts
+            .get_mut(host)
+            .ok_or(Error::CredentialStore(format!("no accounts for {}", host)))?;
+        if !accounts.accounts.contains_key(account) {
+            return Err(Error::CredentialStore(format!("no account {} for {}", account, host)));
+        }
+        accounts.active = Some(account.to_string());
+        Ok(())
+    }
+
+    pub fn accounts(&self) -> Vec<(&str, &str, bool)> {
+        let mut accounts = Vec::new();
+        for (host, h) in &self.hosts {
+            for account in h.accounts.keys() {
+                accounts.push((&**host, &**account, h.active.as_ref() == Some(account)));
+            }
+        }
+        accounts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secret::Secret;
+    use sodiumoxide;
+
+    fn store() -> CredentialStore {
+        CredentialStore {
+            hosts: BTreeMap::new(),
+            passphrase: Secret::new("passphrase"),
+        }
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let _ = sodiumoxide::init();
+        let mut store = store();
+        store.add("github.com", "me", "ghp_one");
+        store.add("gitlab.com", "work", "glpat-two");
+
+        let file = seal(&store.hosts, &store.passphrase).unwrap();
+        assert!(!file.data.contains("ghp_one"));
+        let hosts = unseal(&file, &Secret::new("passphrase")).unwrap();
+        assert_eq!(hosts["github.com"].accounts["me"], Secret::new("ghp_one"));
+        assert_eq!(hosts["gitlab.com"].active, Some("work".to_string()));
+
+        match unseal(&file, &Secret::new("wrong")) {
+            Err(Error::CredentialStore(_)) => {}
+            _ => panic!("opened the store with the wrong passphrase"),
+        }
+    }
+
+    #[test]
+    fn active_account_handoff() {
+        let mut store = store();
+        store.add("github.com", "me", "one");
+        store.add("github.com", "work", "two");
+        assert_eq!(store.token("github.com", None), Some(&Secret::new("one")));
+        assert_eq!(store.token("github.com", Some("work")), Some(&Secret::new("two")));
+
+        store.switch("github.com", "work").unwrap();
+        assert_eq!(store.token("github.com", None), Some(&Secret::new("two")));
+        assert!(store.switch("github.com", "nobody").is_err());
+        assert!(store.switch("gitlab.com", "work").is_err());
+
+        store.remove("github.com", "work").unwrap();
+        assert_eq!(store.token("github.com", None), Some(&Secret::new("one")));
+        assert_eq!(store.accounts(), vec![("github.com", "me", true)]);
+
+        store.remove("github.com", "me").unwrap();
+        assert_eq!(store.token("github.com", None), None);
+        assert!(store.accounts().is_empty());
+        assert!(store.remove("github.com", "me").is_err());
+    }
+}
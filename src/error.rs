@@ -5,6 +5,11 @@ use url;
 use git2;
 use notify;
 use tempfile;
+use hyper;
+use native_tls;
+use openssl;
+use toml;
+use base64;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -17,10 +22,28 @@ pub enum Error {
     Deserialize(serde::de::value::Error),
     Notify(notify::Error),
     Tempfile(tempfile::PersistError),
+    Http(hyper::Error),
+    Tls(native_tls::Error),
+    Ssl(openssl::error::ErrorStack),
+    Api(u16, String),
+    Toml(toml::de::Error),
+    Base64(base64::DecodeError),
     Nom,
     MissingParameter(String),
+    InvalidValue(String),
+    InvalidManifest(String),
+    InvalidDeployKey(String),
+    InvalidPublicKey,
+    Unsupported(String),
+    InvalidProxy(String),
+    OtpRequired,
+    OAuth(String),
+    MissingScope(String),
+    CredentialStore(String),
     InvalidTargetDir,
     RepositoryBare,
+    RepositoryExists,
+    Panicked(String),
 }
 
 impl std::fmt::Display for Error {
@@ -33,32 +56,34 @@ impl std::fmt::Display for Error {
             Error::Git(ref e) => e.fmt(f),
             Error::Notify(ref e) => e.fmt(f),
             Error::Tempfile(ref e) => e.fmt(f),
+            Error::Http(ref e) => e.fmt(f),
+            Error::Tls(ref e) => e.fmt(f),
+            Error::Ssl(ref e) => e.fmt(f),
+            Error::Api(status, ref m) => write!(f, "API error ({}): {}", status, m),
+            Error::Toml(ref e) => e.fmt(f),
+            Error::Base64(ref e) => e.fmt(f),
             Error::Nom => write!(f, "Parse error"),
             Error::MissingParameter(ref p) => write!(f, "Missing parameter: {}", p),
+            Error::InvalidValue(ref p) => write!(f, "Invalid value: {}", p),
+            Error::InvalidManifest(ref p) => write!(f, "Invalid manifest value: {}", p),
+            Error::InvalidDeployKey(ref p) => write!(f, "Invalid deploy key: {}", p),
+            Error::InvalidPublicKey => write!(f, "Repository public key is invalid"),
+            Error::Unsupported(ref p) => write!(f, "Not supported by this forge: {}", p),
+            Error::InvalidProxy(ref p) => write!(f, "Invalid proxy: {}", p),
+            Error::OtpRequired => write!(f, "Two-factor authentication code required"),
+            Error::OAuth(ref e) => write!(f, "Login failed: {}", e),
+            Error::MissingScope(ref s) => write!(f, "The token is missing the '{}' scope", s),
+            Error::CredentialStore(ref e) => write!(f, "Credential store: {}", e),
             Error::InvalidTargetDir => write!(f, "Target directory is invalid"),
             Error::RepositoryBare => write!(f, "Git repository is bare"),
+            Error::RepositoryExists => write!(f, "Target directory is already a git repository"),
+            Error::Panicked(ref p) => write!(f, "Unexpected failure: {}", p),
         }
     }
 }
 
 impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::Json(ref e) => e.description(),
-            Error::Io(ref e) => e.description(),
-            Error::Deserialize(ref e) => e.description(),
-            Error::Url(ref e) => e.description(),
-            Error::Git(ref e) => e.description(),
-            Error::Notify(ref e) => e.description(),
-            Error::Tempfile(ref e) => e.description(),
-            Error::Nom => "Parse Error",
-            Error::MissingParameter(_) => "Missing parameter",
-            Error::InvalidTargetDir => "Target directory is invalid",
-            Error::RepositoryBare => "Git repository is bare",
-        }
-    }
-
-    fn cause(&self) -> Option<&std::error::Error> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
             Error::Json(ref e) => Some(e),
             Error::Io(ref e) => Some(e),
@@ -67,6 +92,11 @@ impl std::error::Error for Error {
             Error::Git(ref e) => Some(e),
             Error::Notify(ref e) => Some(e),
             Error::Tempfile(ref e) => Some(e),
+            Error::Http(ref e) => Some(e),
+            Error::Tls(ref e) => Some(e),
+            Error::Ssl(ref e) => Some(e),
+            Error::Toml(ref e) => Some(e),
+            Error::Base64(ref e) => Some(e),
             _ => None,
         }
     }
@@ -107,3 +137,33 @@ impl From<tempfile::PersistError> for Error {
         Error::Tempfile(e)
     }
 }
+
+impl From<hyper::Error> for Error {
+    fn from(e: hyper::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<native_tls::Error> for Error {
+    fn from(e: native_tls::Error) -> Self {
+        Error::Tls(e)
+    }
+}
+
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        Error::Ssl(e)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Toml(e)
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(e: base64::DecodeError) -> Self {
+        Error::Base64(e)
+    }
+}
@@ -0,0 +1,132 @@
+use error::{Error, Result};
+use github::{GitHubClient, RepoSettings, TokenInfo};
+use gitea::GiteaClient;
+use gitlab::GitLabClient;
+use http::HttpOptions;
+use local::LocalForge;
+use manifest::{CreateRequest, ResolvedParams};
+use secret::Secret;
+use summary::Summary;
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+    Gitea,
+    Local,
+}
+
+impl FromStr for ForgeKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ForgeKind> {
+        match s {
+            "github" => Ok(ForgeKind::GitHub),
+            "gitlab" => Ok(ForgeKind::GitLab),
+            "gitea" | "forgejo" => Ok(ForgeKind::Gitea),
+            "local" => Ok(ForgeKind::Local),
+            _ => Err(Error::Unsupported(format!("forge '{}'", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteRepository {
+    pub full_name: String,
+    pub clone_url: String,
+    pub ssh_url: String,
+    pub web_url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForkedRepository {
+    pub repository: RemoteRepository,
+    pub parent: Option<RemoteRepository>,
+}
+
+pub trait Forge {
+    fn create_repository(&self, request: &CreateRequest) -> Result<RemoteRepository>;
+    fn repository(&self, repo: &str) -> Result<RemoteRepository>;
+    fn delete_repository(&self, repo: &str) -> Result<()>;
+    fn update_settings(&self, repo: &str, settings: &RepoSettings) -> Result<()>;
+
+    fn apply_settings(&self,
+                      request: &CreateRequest,
+                      _resolved: &ResolvedParams,
+                      _full_name: &str,
+                      key: Option<(&str, bool)>,
+                      summary: &mut Summary) {
+        if request.has_github_settings() || key.is_some() {
+            let unsupported = Error::Unsupported("collaborators, teams, labels, webhooks, deploy \
+                                                  keys and Actions settings"
+                .into());
+            summary.record::<_, ()>("Apply repository settings", Err(unsupported));
+        }
+    }
+
+    fn fork_repository(&self,
+                       _repo: &str,
+                       _organization: Option<&str>,
+                       _name: Option<&str>)
+                       -> Result<ForkedRepository> {
+        Err(Error::Unsupported("fork".into()))
+    }
+
+    fn set_otp(&self, _otp: Secret) -> Result<()> {
+        Err(Error::Unsupported("two-factor authentication codes".into()))
+    }
+
+    fn token_info(&self) -> Result<TokenInfo> {
+        Err(Error::Unsupported("whoami".into()))
+    }
+
+    fn check_scopes(&self, _request: &CreateRequest) -> Result<()> {
+        Ok(())
+    }
+
+    fn list_templates(&self) -> Result<Vec<String>> {
+        Err(Error::Unsupported("templates".into()))
+    }
+
+    fn gitignore_template(&self, _name: &str) -> Result<String> {
+        Err(Error::Unsupported("gitignore templates".into()))
+    }
+
+    fn license_template(&self, _key: &str) -> Result<String> {
+        Err(Error::Unsupported("license templates".into()))
+    }
+}
+
+pub fn create(forge: &dyn Forge, request: &CreateRequest) -> Result<RemoteRepository> {
+    forge.check_scopes(request)?;
+    forge.create_repository(request)
+}
+
+pub fn fork(forge: &dyn Forge,
+            repo: &str,
+            organization: Option<&str>,
+            name: Option<&str>)
+            -> Result<ForkedRepository> {
+    let request = CreateRequest {
+        organization: organization.unwrap_or("").into(),
+        ..CreateRequest::default()
+    };
+    forge.check_scopes(&request)?;
+    forge.fork_repository(repo, organization, name)
+}
+
+pub fn connect(kind: ForgeKind,
+               url: Option<&str>,
+               auth: &Secret,
+               options: &HttpOptions)
+               -> Result<Arc<dyn Forge + Send + Sync>> {
+    match kind {
+        ForgeKind::GitHub => Ok(Arc::new(GitHubClient::with_url(url, auth.clone(), options)?)),
+        ForgeKind::GitLab => Ok(Arc::new(GitLabClient::new(url, auth.clone(), options)?)),
+        ForgeKind::Gitea => Ok(Arc::new(GiteaClient::new(url, auth.clone(), options)?)),
+        ForgeKind::Local => Ok(Arc::new(LocalForge::new(url)?)),
+    }
+}
@@ -1,10 +1,18 @@
 extern crate rpassword;
 
-use git2::{Config, Repository, BranchType};
+use git2::{self, Config, Repository, BranchType, RemoteCallbacks, Cred, FetchOptions, ProxyOptions};
+use git2::build::RepoBuilder;
 use error::{Error, Result};
+use http::HttpOptions;
+use secret::Secret;
 use url::Url;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::Write;
 use std::env;
+use std::cell::RefCell;
+use std::sync::Once;
+use tempdir::TempDir;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum GitMode {
@@ -12,21 +20,42 @@ pub enum GitMode {
     Clone,
     Remote,
     Push,
+    Init,
     Rebase,
 }
 
 pub fn get_config_value(key: &str) -> Result<String> {
-    let conf = try!(Config::open_default());
+    let conf = Config::open_default()?;
     conf.get_string(key).map_err(|e| e.into())
 }
 
+static CA_INFO: Once = Once::new();
+
+fn proxy_options(repo_url: &str) -> ProxyOptions<'static> {
+    let options = HttpOptions::from_env();
+    if let Some(ref ca_info) = options.ca_info {
+        // libgit2 keeps the certificate locations in global state, so set them only once
+        CA_INFO.call_once(|| {
+            if let Err(e) = unsafe { git2::opts::set_ssl_cert_file(&**ca_info) } {
+                warn!("Could not load the certificates in {}: {}", ca_info, e);
+            }
+        });
+    }
+
+    let mut proxy = ProxyOptions::new();
+    if let Some(url) = options.proxy_for(repo_url) {
+        proxy.url(&url);
+    }
+    proxy
+}
+
 pub fn clone(repo_url: &str, target_dir: Option<&str>) -> Result<String> {
-    let mut url = try!(Url::parse(repo_url));
-    try!(url.set_host(Some("localhost")));
+    let mut url = Url::parse(repo_url)?;
+    url.set_host(Some("localhost"))?;
     let repo_path = target_dir.map(|x| x.to_string()).or_else(|| {
         match url.to_file_path()
             .ok()
-            .map_or(None, |x| {
+            .and_then(|x| {
                 x.file_stem()
                     .map(|x| x.to_string_lossy().into_owned())
             }) {
@@ -43,7 +72,9 @@ pub fn clone(repo_url: &str, target_dir: Option<&str>) -> Result<String> {
     let repo_path = Path::new(&repo_path);
 
     if !repo_path.exists() || (repo_path.is_dir() && repo_path.read_dir().unwrap().count() == 0) {
-        let repo = try!(Repository::clone(repo_url, repo_path));
+        let mut fetch = FetchOptions::new();
+        fetch.proxy_options(proxy_options(repo_url));
+        let repo = RepoBuilder::new().fetch_options(fetch).clone(repo_url, repo_path)?;
         get_repo_dir(&repo)
     } else {
         Err(Error::InvalidTargetDir)
@@ -53,88 +84,391 @@ pub fn clone(repo_url: &str, target_dir: Option<&str>) -> Result<String> {
 fn find_repository(target_dir: Option<&str>) -> Result<Repository> {
     let repo;
     if let Some(target_dir) = target_dir {
-        repo = try!(Repository::open(target_dir));
+        repo = Repository::open(target_dir)?;
     } else {
-        let dir = try!(env::current_dir());
-        repo = try!(Repository::discover(dir));
+        let dir = env::current_dir()?;
+        repo = Repository::discover(dir)?;
     }
 
     Ok(repo)
 }
 
 fn get_repo_dir(repo: &Repository) -> Result<String> {
-    let path = try!(repo.workdir().ok_or(Error::RepositoryBare));
-    Ok(path.to_string_lossy().to_owned().to_string())
+    let path = repo.workdir().ok_or(Error::RepositoryBare)?;
+    Ok(path.to_string_lossy().into_owned())
 }
 
 pub fn get_repo_name(target_dir: Option<&str>) -> Result<String> {
-    let repo = try!(find_repository(target_dir));
-    let path = try!(repo.workdir().ok_or(Error::RepositoryBare));
+    let repo = find_repository(target_dir)?;
+    let path = repo.workdir().ok_or(Error::RepositoryBare)?;
     path.file_name()
         .ok_or(Error::RepositoryBare)
-        .map(|x| x.to_string_lossy().to_owned().to_string())
+        .map(|x| x.to_string_lossy().into_owned())
+}
+
+fn target_path(target_dir: Option<&str>) -> Result<PathBuf> {
+    match target_dir {
+        Some(target_dir) => Ok(PathBuf::from(target_dir)),
+        None => env::current_dir().map_err(|e| e.into()),
+    }
+}
+
+pub fn get_dir_name(target_dir: Option<&str>) -> Result<String> {
+    let path = target_path(target_dir)?;
+    let path = path.canonicalize().unwrap_or(path);
+    path.file_name()
+        .ok_or(Error::InvalidTargetDir)
+        .map(|x| x.to_string_lossy().into_owned())
+}
+
+fn write_files(dir: &Path,
+               files: &[(String, String)],
+               replace: &[String])
+               -> Result<Vec<String>> {
+    let mut written = Vec::new();
+    for (name, contents) in files {
+        let path = dir.join(name);
+        if path.exists() && !replace.contains(name) {
+            continue;
+        }
+        let mut file = File::create(path)?;
+        file.write_all(contents.as_bytes())?;
+        written.push(name.clone());
+    }
+    Ok(written)
+}
+
+pub fn local_dir(target_dir: Option<&str>) -> Result<PathBuf> {
+    match find_repository(target_dir) {
+        Ok(repo) => repo.workdir().map(|x| x.to_path_buf()).ok_or(Error::RepositoryBare),
+        Err(_) => target_path(target_dir),
+    }
+}
+
+fn has_commits(repo: &Repository) -> bool {
+    repo.head().ok().and_then(|head| head.target()).is_some()
+}
+
+pub fn check_init(target_dir: Option<&str>) -> Result<()> {
+    let path = target_path(target_dir)?;
+    match Repository::open(&path) {
+        Ok(ref repo) if has_commits(repo) => Err(Error::RepositoryExists),
+        Ok(repo) => repo.signature().map(|_| ()).map_err(|e| e.into()),
+        Err(_) => {
+            let conf = Config::open_default()?;
+            conf.get_string("user.name")?;
+            conf.get_string("user.email")?;
+            Ok(())
+        }
+    }
+}
+
+pub fn init(target_dir: Option<&str>,
+            branch: &str,
+            files: &[(String, String)],
+            replace: &[String])
+            -> Result<String> {
+    let path = target_path(target_dir)?;
+    check_init(target_dir)?;
+    fs::create_dir_all(&path)?;
+    // An empty repository left behind by an earlier run is picked up again
+    let repo = match Repository::open(&path) {
+        Ok(repo) => repo,
+        Err(_) => Repository::init(&path)?,
+    };
+    repo.set_head(&format!("refs/heads/{}", branch))?;
+    write_files(&path, files, replace)?;
+
+    {
+        let mut index = repo.index()?;
+        index.add_all(vec!["*"], git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = repo.signature()?;
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])?;
+    }
+    get_repo_dir(&repo)
+}
+
+pub fn commit_files(target_dir: Option<&str>,
+                    files: &[(String, String)],
+                    replace: &[String],
+                    message: &str)
+                    -> Result<Vec<String>> {
+    let repo = find_repository(target_dir)?;
+    let workdir = repo.workdir().ok_or(Error::RepositoryBare)?.to_path_buf();
+    let written = write_files(&workdir, files, replace)?;
+    if written.is_empty() {
+        return Ok(written);
+    }
+
+    let mut index = repo.index()?;
+    for name in &written {
+        index.add_path(Path::new(name))?;
+    }
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = repo.signature()?;
+    let parent = repo.head()
+        .ok()
+        .and_then(|head| head.target())
+        .and_then(|oid| repo.find_commit(oid).ok());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+    Ok(written)
+}
+
+fn current_branch(repo: &Repository) -> String {
+    repo.head()
+        .ok()
+        .and_then(|head| head.shorthand().map(|x| x.to_string()))
+        .unwrap_or("master".into())
 }
 
 fn set_upstream(repo: &mut Repository, local_branch: &str, remote_branch: &str) -> Result<()> {
-    let mut master = try!(repo.find_branch(local_branch, BranchType::Local));
+    let mut master = repo.find_branch(local_branch, BranchType::Local)?;
     master.set_upstream(Some(remote_branch)).map_err(|x| x.into())
 }
 
+fn set_remote(repo: &Repository, name: &str, repo_url: &str) -> Result<()> {
+    let remote = repo.find_remote(name).ok();
+    let mut remote = if let Some(remote) = remote {
+        repo.remote_set_url(name, repo_url)?;
+        remote
+    } else {
+        repo.remote(name, repo_url)?
+    };
+    let refspecs: &[&str] = &[];
+    remote.fetch(refspecs,
+                      Some(FetchOptions::new().proxy_options(proxy_options(repo_url))),
+                      None)?;
+    Ok(())
+}
+
 pub fn remotes(repo_url: &str, target_dir: Option<&str>) -> Result<String> {
-    let repo = try!(find_repository(target_dir));
-    {
-        let remote = repo.find_remote("origin").ok();
-        let mut remote = if let Some(remote) = remote {
-            try!(repo.remote_set_url("origin", repo_url));
-            remote
-        } else {
-            try!(repo.remote("origin", repo_url))
-        };
-        try!(remote.fetch(&[], None, None));
-    }
+    let repo = find_repository(target_dir)?;
+    set_remote(&repo, "origin", repo_url)?;
     let mut repo = repo;
-    set_upstream(&mut repo, "master", "origin/master").ok();
+    let branch = current_branch(&repo);
+    set_upstream(&mut repo, &branch, &format!("origin/{}", branch)).ok();
     get_repo_dir(&repo)
 }
 
-pub fn push(target_dir: Option<&str>,
-            username: Option<&str>,
-            password: Option<&str>)
-            -> Result<String> {
-    use git2::{PushOptions, RemoteCallbacks, Cred};
+pub fn add_remote(name: &str, repo_url: &str, target_dir: Option<&str>) -> Result<String> {
+    let repo = find_repository(target_dir)?;
+    set_remote(&repo, name, repo_url)?;
+    get_repo_dir(&repo)
+}
+
+fn push_callbacks<'a>(username: Option<&'a str>,
+                      password: Option<&'a Secret>)
+                      -> RemoteCallbacks<'a> {
     use std::io::stdin;
-    let repo = try!(find_repository(target_dir));
-    {
-        let mut remote = try!(repo.find_remote("origin"));
 
-        let mut cbs = RemoteCallbacks::new();
-        cbs.credentials(|_, _, _| {
-            // url, username from url, allowed cred types
-            let username = if username.is_none() {
+    let mut cbs = RemoteCallbacks::new();
+    cbs.credentials(move |_, _, _| {
+        // url, username from url, allowed cred types
+        let username = match username {
+            Some(username) => username.to_owned(),
+            None => {
                 let mut buf = String::new();
                 let _ = stdin().read_line(&mut buf);
                 buf
-            } else {
-                username.unwrap().to_owned()
-            };
+            }
+        };
 
-            let password = if password.is_none() {
+        let password = match password {
+            Some(password) => password.clone(),
+            None => {
                 println!("Password: ");
-                rpassword::read_password().unwrap()
-            } else {
-                password.unwrap().to_owned()
-            };
-            Cred::userpass_plaintext(username.trim(), &*password)
-        });
-        cbs.transfer_progress(|_| true);
-        cbs.sideband_progress(|_| true);
-        cbs.update_tips(|_, _, _| true);
-        cbs.certificate_check(|_, _| true);
+                Secret::new(rpassword::read_password()
+                    .map_err(|e| git2::Error::from_str(&e.to_string()))?)
+            }
+        };
+        Cred::userpass_plaintext(username.trim(), password.expose())
+    });
+    cbs.transfer_progress(|_| true);
+    cbs.sideband_progress(|_| true);
+    cbs.update_tips(|_, _, _| true);
+    cbs
+}
+
+pub fn push(target_dir: Option<&str>,
+            username: Option<&str>,
+            password: Option<&Secret>)
+            -> Result<String> {
+    use git2::PushOptions;
+    let repo = find_repository(target_dir)?;
+    let branch = current_branch(&repo);
+    {
+        let mut remote = repo.find_remote("origin")?;
+        let url = remote.url().unwrap_or("").to_string();
+        let cbs = push_callbacks(username, password);
 
-        try!(remote.push(&["refs/heads/master:refs/heads/master"],
-                         Some(PushOptions::new().remote_callbacks(cbs))));
+        let refspec = format!("refs/heads/{}:refs/heads/{}", branch, branch);
+        remote.push(&[&*refspec],
+                         Some(PushOptions::new()
+                             .remote_callbacks(cbs)
+                             .proxy_options(proxy_options(&url))))?;
     }
     let mut repo = repo;
-    try!(set_upstream(&mut repo, "master", "origin/master"));
+    set_upstream(&mut repo, &branch, &format!("origin/{}", branch))?;
     get_repo_dir(&repo)
 }
+
+pub fn get_url_name(repo_url: &str) -> Option<String> {
+    let name = repo_url.trim_end_matches('/')
+        .rsplit(&['/', ':'][..])
+        .next()
+        .unwrap_or("");
+    let name = name.trim_end_matches(".git");
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+pub struct MirrorResult {
+    pub refs: usize,
+    pub rejected: Vec<(String, String)>,
+}
+
+// Only branches and tags; GitHub refuses pushes to its read-only refs/pull/*
+const MIRROR_REFSPECS: [&str; 2] = ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+fn is_mirrored(name: &str) -> bool {
+    name.starts_with("refs/heads/") || name.starts_with("refs/tags/")
+}
+
+pub fn mirror(source_url: &str,
+              repo_url: &str,
+              username: Option<&str>,
+              password: Option<&Secret>)
+              -> Result<MirrorResult> {
+    use git2::PushOptions;
+
+    let dir = TempDir::new("create-gh-repo")?;
+    let repo = Repository::init_bare(dir.path())?;
+    {
+        let mut source = repo.remote_with_fetch("source", source_url, MIRROR_REFSPECS[0])?;
+        let mut cbs = RemoteCallbacks::new();
+        let mut attempts = 0;
+        cbs.credentials(|url, username, allowed| {
+            if allowed.contains(git2::CredentialType::USERNAME) {
+                return Cred::username(username.unwrap_or("git"));
+            }
+            // libgit2 asks again for as long as the remote rejects what we return
+            attempts += 1;
+            if attempts > 1 {
+                return Err(git2::Error::from_str(&format!("authentication failed for {}",
+                                                           url)));
+            }
+            if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                let config = repo.config()?;
+                Cred::credential_helper(&config, url, username)
+            } else {
+                Cred::ssh_key_from_agent(username.unwrap_or("git"))
+            }
+        });
+        source.fetch(&MIRROR_REFSPECS,
+                          Some(FetchOptions::new()
+                              .remote_callbacks(cbs)
+                              .proxy_options(proxy_options(source_url))),
+                          None)?;
+    }
+
+    let mut refspecs = Vec::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        if let Some(name) = reference.name() {
+            if is_mirrored(name) {
+                refspecs.push(format!("+{}:{}", name, name));
+            }
+        }
+    }
+
+    let rejected = RefCell::new(Vec::new());
+    {
+        let mut origin = repo.remote("origin", repo_url)?;
+        let mut cbs = push_callbacks(username, password);
+        cbs.push_update_reference(|name, status| {
+            if let Some(status) = status {
+                rejected.borrow_mut().push((name.to_string(), status.to_string()));
+            }
+            Ok(())
+        });
+        let refspecs: Vec<&str> = refspecs.iter().map(|x| &**x).collect();
+        origin.push(&refspecs,
+                         Some(PushOptions::new()
+                             .remote_callbacks(cbs)
+                             .proxy_options(proxy_options(repo_url))))?;
+    }
+
+    Ok(MirrorResult {
+        refs: refspecs.len(),
+        rejected: rejected.into_inner(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_names() {
+        assert_eq!(get_url_name("https://github.com/me/repo.git"), Some("repo".to_string()));
+        assert_eq!(get_url_name("https://github.com/me/repo/"), Some("repo".to_string()));
+        assert_eq!(get_url_name("git@github.com:me/repo.git"), Some("repo".to_string()));
+        assert_eq!(get_url_name("git@host:repo"), Some("repo".to_string()));
+        assert_eq!(get_url_name("file:///srv/git/repo.git"), Some("repo".to_string()));
+        assert_eq!(get_url_name("https://github.com/me/.git"), None);
+        assert_eq!(get_url_name(""), None);
+    }
+
+    #[test]
+    fn mirrored_refs() {
+        assert!(is_mirrored("refs/heads/master"));
+        assert!(is_mirrored("refs/tags/v1.0"));
+        assert!(!is_mirrored("refs/pull/1/head"));
+        assert!(!is_mirrored("refs/remotes/origin/master"));
+        assert!(!is_mirrored("HEAD"));
+    }
+
+    #[test]
+    fn init_commits_files() {
+        let dir = TempDir::new("create-gh-repo-test").unwrap();
+        let path = dir.path().join("project");
+        {
+            // Leave an empty repository behind, as an interrupted run would
+            let repo = Repository::init(&path).unwrap();
+            let mut conf = repo.config().unwrap();
+            conf.set_str("user.name", "Test User").unwrap();
+            conf.set_str("user.email", "test@example.com").unwrap();
+        }
+        fs::create_dir_all(path.join("target")).unwrap();
+        File::create(path.join("target").join("build.log")).unwrap();
+
+        let target = path.to_str();
+        let files = vec![(".gitignore".to_string(), "/target\n".to_string()),
+                         ("README.md".to_string(), "# project\n".to_string())];
+        init(target, "trunk", &files, &[]).unwrap();
+
+        let repo = Repository::open(&path).unwrap();
+        let head = repo.head().unwrap();
+        assert_eq!(head.shorthand(), Some("trunk"));
+        let commit = head.peel_to_commit().unwrap();
+        assert_eq!(commit.message(), Some("Initial commit"));
+        assert_eq!(commit.author().name(), Some("Test User"));
+        assert_eq!(commit.committer().email(), Some("test@example.com"));
+
+        let tree = commit.tree().unwrap();
+        let names: Vec<_> = tree.iter().map(|entry| entry.name().unwrap().to_string()).collect();
+        assert_eq!(names, vec![".gitignore".to_string(), "README.md".to_string()]);
+
+        match init(target, "trunk", &files, &[]) {
+            Err(Error::RepositoryExists) => {}
+            other => panic!("expected RepositoryExists, got {:?}", other),
+        }
+    }
+}
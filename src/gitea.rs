@@ -0,0 +1,185 @@
+use error::{Error, Result};
+use forge::{Forge, RemoteRepository};
+use github::{self, RepoSettings, USER_AGENT};
+use http::{Client, HttpOptions, Method, Request};
+use manifest::CreateRequest;
+use secret::Secret;
+
+use hyper::header::UserAgent;
+use serde::{Serialize, Deserialize};
+
+#[derive(Deserialize, Debug)]
+struct Repository {
+    full_name: String,
+    clone_url: String,
+    ssh_url: String,
+    html_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitignoreTemplate {
+    source: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LicenseTemplate {
+    body: String,
+}
+
+#[derive(Serialize, Debug)]
+struct CreateRepository<'a> {
+    name: &'a str,
+    description: &'a str,
+    private: bool,
+    auto_init: bool,
+    #[serde(skip_serializing_if = "is_empty")]
+    gitignores: &'a str,
+    #[serde(skip_serializing_if = "is_empty")]
+    license: &'a str,
+    #[serde(skip_serializing_if = "is_empty")]
+    readme: &'a str,
+    #[serde(skip_serializing_if = "is_empty")]
+    default_branch: &'a str,
+    #[serde(skip_serializing_if = "is_empty")]
+    trust_model: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct EditRepository<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    website: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    private: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has_issues: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has_wiki: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_branch: Option<&'a str>,
+}
+
+fn is_empty(value: &&str) -> bool {
+    value.is_empty()
+}
+
+pub struct GiteaClient {
+    client: Client,
+    url: String,
+    token: Secret,
+}
+
+impl GiteaClient {
+    pub fn new<S>(url: Option<&str>, token: S, options: &HttpOptions) -> Result<GiteaClient>
+        where S: Into<Secret>
+    {
+        let url = url.ok_or(Error::MissingParameter("forge-url".into()))?
+            .trim_end_matches('/');
+        Ok(GiteaClient {
+            client: Client::new(url, options)?,
+            url: url.to_string(),
+            token: token.into(),
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> Request {
+        self.client
+            .request(method, format!("{}/api/v1{}", self.url, path))
+            .raw_header("Authorization", format!("token {}", self.token.expose()))
+            .header(UserAgent(USER_AGENT.to_string()))
+    }
+
+    fn get<T>(&self, path: &str) -> Result<T>
+        where T: Deserialize
+    {
+        let mut res = self.client.send(&self.request(Method::Get, path))?;
+        res.json()
+    }
+
+    fn send_json<B, T>(&self, method: Method, path: &str, body: &B) -> Result<T>
+        where B: Serialize,
+              T: Deserialize
+    {
+        let mut res = self.client.send(&self.request(method, path).json(body)?)?;
+        res.json()
+    }
+}
+
+impl From<Repository> for RemoteRepository {
+    fn from(repo: Repository) -> RemoteRepository {
+        RemoteRepository {
+            full_name: repo.full_name,
+            clone_url: repo.clone_url,
+            ssh_url: repo.ssh_url,
+            web_url: repo.html_url,
+        }
+    }
+}
+
+impl Forge for GiteaClient {
+    fn create_repository(&self, request: &CreateRequest) -> Result<RemoteRepository> {
+        if !request.template.is_empty() {
+            return Err(Error::Unsupported("template".into()));
+        }
+
+        let path = if request.organization.is_empty() {
+            "/user/repos".to_string()
+        } else {
+            format!("/orgs/{}/repos", github::escape(&request.organization))
+        };
+        let create = CreateRepository {
+            name: &request.name,
+            description: &request.description,
+            private: request.private,
+            auto_init: request.auto_init,
+            gitignores: &request.gitignores,
+            license: &request.license,
+            readme: if request.auto_init { "Default" } else { "" },
+            default_branch: &request.default_branch,
+            trust_model: &request.trust_model,
+        };
+        let repo: Repository = self.send_json(Method::Post, &path, &create)?;
+        Ok(repo.into())
+    }
+
+    fn repository(&self, repo: &str) -> Result<RemoteRepository> {
+        let repo: Repository = self.get(&format!("/repos/{}", repo))?;
+        Ok(repo.into())
+    }
+
+    fn delete_repository(&self, repo: &str) -> Result<()> {
+        let path = format!("/repos/{}", repo);
+        self.client.send(&self.request(Method::Delete, &path))?;
+        Ok(())
+    }
+
+    fn update_settings(&self, repo: &str, settings: &RepoSettings) -> Result<()> {
+        let edit = EditRepository {
+            name: settings.name.as_deref(),
+            description: settings.description.as_deref(),
+            website: settings.homepage.as_deref(),
+            private: settings.private,
+            has_issues: settings.has_issues,
+            has_wiki: settings.has_wiki,
+            default_branch: settings.default_branch.as_deref(),
+        };
+        let path = format!("/repos/{}", repo);
+        let _: Repository = self.send_json(Method::Patch, &path, &edit)?;
+        Ok(())
+    }
+
+    fn gitignore_template(&self, name: &str) -> Result<String> {
+        let template: GitignoreTemplate =
+            self.get(&format!("/gitignore/templates/{}", github::escape(name)))?;
+        Ok(template.source)
+    }
+
+    fn license_template(&self, key: &str) -> Result<String> {
+        let license: LicenseTemplate =
+            self.get(&format!("/licenses/{}", github::escape(key)))?;
+        Ok(license.body)
+    }
+}
@@ -0,0 +1,808 @@
+use error::{Error, Result};
+use deploy_key;
+use forge::{Forge, ForkedRepository, RemoteRepository};
+use http::{self, Client, HttpOptions, Method, Request, Response};
+use labels;
+use manifest::{CreateRequest, ResolvedParams};
+use secret::Secret;
+use summary::Summary;
+
+use hyper::header::{UserAgent, Authorization, Basic};
+use serde::{Serialize, Deserialize};
+use base64;
+use sodiumoxide::crypto::box_;
+use sodiumoxide::crypto::sealedbox;
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+pub const API_ROOT: &str = "https://api.github.com";
+pub const USER_AGENT: &str = concat!("create-gh-repo/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Serialize, Debug)]
+struct PermissionRequest<'a> {
+    permission: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct HookRequest<'a> {
+    name: &'a str,
+    active: bool,
+    events: &'a [String],
+    config: HookConfig<'a>,
+}
+
+#[derive(Serialize, Debug)]
+struct HookConfig<'a> {
+    url: &'a str,
+    content_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<&'a Secret>,
+}
+
+#[derive(Serialize, Debug)]
+struct DeployKeyRequest<'a> {
+    title: &'a str,
+    key: &'a str,
+    read_only: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ActionsPublicKey {
+    key_id: String,
+    key: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ActionsSecretRequest<'a> {
+    encrypted_value: &'a str,
+    key_id: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct ActionsVariableRequest<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Repository {
+    pub name: String,
+    pub full_name: String,
+    pub clone_url: String,
+    #[serde(default)]
+    pub ssh_url: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default)]
+    pub default_branch: String,
+    #[serde(default)]
+    pub is_template: bool,
+    pub parent: Option<Box<Repository>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct User {
+    pub login: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitignoreTemplate {
+    source: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LicenseTemplate {
+    body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    pub reset: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    pub login: String,
+    pub scopes: Option<Vec<String>>,
+    pub expires: Option<String>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RepoSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_issues: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_wiki: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_downloads: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_branch: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct GenerateRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<&'a str>,
+    name: &'a str,
+    description: &'a str,
+    private: bool,
+    include_all_branches: bool,
+}
+
+impl<'a> From<&'a CreateRequest> for GenerateRequest<'a> {
+    fn from(request: &'a CreateRequest) -> GenerateRequest<'a> {
+        GenerateRequest {
+            owner: if request.organization.is_empty() {
+                None
+            } else {
+                Some(&*request.organization)
+            },
+            name: &request.name,
+            description: &request.description,
+            private: request.private,
+            include_all_branches: request.include_all_branches,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ForkRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    organization: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+}
+
+pub struct GitHubClient {
+    client: Client,
+    api_root: String,
+    auth: Secret,
+    otp: Mutex<Option<Secret>>,
+}
+
+impl GitHubClient {
+    pub fn new<S>(auth: S) -> Result<GitHubClient>
+        where S: Into<Secret>
+    {
+        GitHubClient::with_options(auth, &HttpOptions::default())
+    }
+
+    pub fn with_options<S>(auth: S, options: &HttpOptions) -> Result<GitHubClient>
+        where S: Into<Secret>
+    {
+        GitHubClient::with_url(None, auth, options)
+    }
+
+    pub fn with_url<S>(url: Option<&str>, auth: S, options: &HttpOptions) -> Result<GitHubClient>
+        where S: Into<Secret>
+    {
+        let api_root = api_root(url);
+        Ok(GitHubClient {
+            client: Client::new(&api_root, options)?,
+            api_root,
+            auth: auth.into(),
+            otp: Mutex::new(None),
+        })
+    }
+
+    pub fn set_otp<S>(&self, otp: S)
+        where S: Into<Secret>
+    {
+        *self.otp.lock().unwrap() = Some(otp.into());
+    }
+
+    fn request(&self, method: Method, path: &str) -> Request {
+        let request = self.client
+            .request(method, format!("{}{}", self.api_root, path))
+            .header(UserAgent(USER_AGENT.to_string()));
+        let auth = self.auth.expose();
+        match auth.find(':') {
+            Some(split) => {
+                request.header(Authorization(Basic {
+                    username: auth[..split].to_string(),
+                    password: Some(auth[split + 1..].to_string()),
+                }))
+            }
+            None => request.header(Authorization(format!("token {}", auth))),
+        }
+    }
+
+    fn send(&self, request: Request) -> Result<Response> {
+        let mut attempts = 0;
+        loop {
+            let otp = self.otp.lock().unwrap().clone();
+            let attempt = match otp {
+                Some(ref otp) => request.clone().raw_header("X-GitHub-OTP", otp.expose().into()),
+                None => request.clone(),
+            };
+            match self.client.send(&attempt) {
+                Err(Error::OtpRequired) if attempts < 3 => {
+                    attempts += 1;
+                    let mut current = self.otp.lock().unwrap();
+                    if *current == otp {
+                        *current = Some(prompt_otp()?);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn get<T>(&self, path: &str) -> Result<T>
+        where T: Deserialize
+    {
+        let mut res = self.send(self.request(Method::Get, path))?;
+        res.json()
+    }
+
+    pub fn get_all<T>(&self, path: &str) -> Result<Vec<T>>
+        where T: Deserialize
+    {
+        let mut items = Vec::new();
+        let mut path = path.to_string();
+        loop {
+            let mut res = self.send(self.request(Method::Get, &path))?;
+            let next = res.header_str("Link").and_then(next_link).map(|x| x.to_string());
+            let mut page: Vec<T> = res.json()?;
+            items.append(&mut page);
+            match next {
+                Some(ref next) if next.starts_with(&*self.api_root) => {
+                    path = next[self.api_root.len()..].to_string();
+                }
+                Some(next) => return Err(Error::InvalidValue(format!("next page {}", next))),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    pub fn post<B, T>(&self, path: &str, body: &B) -> Result<T>
+        where B: Serialize,
+              T: Deserialize
+    {
+        let mut res = self.send(self.request(Method::Post, path).json(body)?)?;
+        res.json()
+    }
+
+    pub fn put<B>(&self, path: &str, body: &B) -> Result<()>
+        where B: Serialize
+    {
+        self.send(self.request(Method::Put, path).json(body)?)?;
+        Ok(())
+    }
+
+    pub fn patch<B, T>(&self, path: &str, body: &B) -> Result<T>
+        where B: Serialize,
+              T: Deserialize
+    {
+        let mut res = self.send(self.request(Method::Patch, path).json(body)?)?;
+        res.json()
+    }
+
+    pub fn delete(&self, path: &str) -> Result<()> {
+        self.send(self.request(Method::Delete, path))?;
+        Ok(())
+    }
+
+    pub fn create_repo(&self, request: &CreateRequest) -> Result<Repository> {
+        if !request.template.is_empty() {
+            let path = format!("/repos/{}/generate", request.template);
+            self.post(&path, &GenerateRequest::from(request))
+        } else if !request.organization.is_empty() {
+            let path = format!("/orgs/{}/repos", request.organization);
+            self.post(&path, request)
+        } else {
+            self.post("/user/repos", request)
+        }
+    }
+
+    pub fn get_repo(&self, repo: &str) -> Result<Repository> {
+        self.get(&format!("/repos/{}", repo))
+    }
+
+    pub fn patch_repo(&self, repo: &str, settings: &RepoSettings) -> Result<Repository> {
+        self.patch(&format!("/repos/{}", repo), settings)
+    }
+
+    pub fn delete_repo(&self, repo: &str) -> Result<()> {
+        self.delete(&format!("/repos/{}", repo))
+    }
+
+    pub fn fork_repo(&self,
+                     repo: &str,
+                     organization: Option<&str>,
+                     name: Option<&str>)
+                     -> Result<Repository> {
+        let request = ForkRequest {
+            organization,
+            name,
+        };
+        self.post(&format!("/repos/{}/forks", repo), &request)
+    }
+
+    pub fn apply_settings(&self,
+                          request: &CreateRequest,
+                          resolved: &ResolvedParams,
+                          full_name: &str,
+                          key: Option<(&str, bool)>,
+                          summary: &mut Summary) {
+        for c in &request.collaborators {
+            let step = format!("Add collaborator {} ({})", c.user, c.permission);
+            summary.record(step, self.add_collaborator(full_name, &c.user, &c.permission));
+        }
+        for t in &request.teams {
+            let step = format!("Add team {} ({})", t.slug, t.permission);
+            let result = if request.organization.is_empty() {
+                Err(Error::MissingParameter("organization".into()))
+            } else {
+                self.add_team(&request.organization, &t.slug, full_name, &t.permission)
+            };
+            summary.record(step, result);
+        }
+        if let Some(ref label_set) = resolved.labels {
+            labels::sync(self, full_name, label_set, summary);
+        }
+        for (h, secret) in request.webhooks.iter().zip(resolved.hook_secrets.iter()) {
+            let step = format!("Add webhook {}", h.url);
+            let result = self.add_webhook(full_name,
+                                          &h.url,
+                                          &h.content_type,
+                                          &h.events,
+                                          secret.as_ref());
+            summary.record(step, result);
+        }
+        if let Some((key_path, write)) = key {
+            let access = if write { "read-write" } else { "read-only" };
+            let step = format!("Add deploy key {} ({})", key_path, access);
+            let comment = format!("deploy@{}", full_name);
+            let result = deploy_key::load_or_generate(key_path, &comment)
+                .and_then(|key| self.add_deploy_key(full_name, &comment, &key, !write));
+            summary.record(step, result);
+        }
+        if !resolved.actions_secrets.is_empty() {
+            let key = summary.record("Fetch Actions public key", self.actions_public_key(full_name));
+            if let Some(key) = key {
+                let secrets = request.actions_secrets.iter().zip(resolved.actions_secrets.iter());
+                for (s, value) in secrets {
+                    let step = format!("Set Actions secret {}", s.name);
+                    let result = self.set_actions_secret(full_name, &key, &s.name, value);
+                    summary.record(step, result);
+                }
+            }
+        }
+        let variables = request.actions_variables.iter().zip(resolved.actions_variables.iter());
+        for (v, value) in variables {
+            let step = format!("Set Actions variable {}", v.name);
+            summary.record(step, self.set_actions_variable(full_name, &v.name, value));
+        }
+    }
+
+    pub fn wait_for_contents(&self, repo: &str) -> Result<()> {
+        let path = format!("/repos/{}/commits?per_page=1", repo);
+        let mut attempts = 0;
+        loop {
+            match self.get::<::serde_json::Value>(&path) {
+                Ok(_) => return Ok(()),
+                Err(Error::Api(404, _)) |
+                Err(Error::Api(409, _)) if attempts < 30 => {
+                    attempts += 1;
+                    thread::sleep(Duration::from_secs(2));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn current_user(&self) -> Result<User> {
+        self.get("/user")
+    }
+
+    pub fn token_info(&self) -> Result<TokenInfo> {
+        let mut res = self.send(self.request(Method::Get, "/user"))?;
+        let scopes = res.header_str("X-OAuth-Scopes").map(|scopes| {
+            scopes.split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect()
+        });
+        let expires = res.header_str("GitHub-Authentication-Token-Expiration")
+            .map(|x| x.to_string());
+        let rate_limit = match (http::header_u64(&res, "X-RateLimit-Limit"),
+                                http::header_u64(&res, "X-RateLimit-Remaining"),
+                                http::header_u64(&res, "X-RateLimit-Reset")) {
+            (Some(limit), Some(remaining), Some(reset)) => {
+                Some(RateLimit {
+                    limit,
+                    remaining,
+                    reset,
+                })
+            }
+            _ => None,
+        };
+        let user: User = res.json()?;
+
+        Ok(TokenInfo {
+            login: user.login,
+            scopes,
+            expires,
+            rate_limit,
+        })
+    }
+
+    pub fn check_scopes(&self, request: &CreateRequest) -> Result<()> {
+        // Installation tokens carry the app's permissions instead of scopes
+        // and are not allowed to read /user
+        if self.auth.expose().starts_with("ghs_") {
+            return Ok(());
+        }
+        let info = match self.token_info() {
+            Ok(info) => info,
+            Err(Error::Api(403, _)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let scopes = match info.scopes {
+            Some(scopes) => scopes,
+            None => return Ok(()),
+        };
+        match missing_scope(&scopes, request) {
+            Some(scope) => Err(Error::MissingScope(scope.into())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn gitignore_template(&self, name: &str) -> Result<String> {
+        let template: GitignoreTemplate =
+            self.get(&format!("/gitignore/templates/{}", escape(name)))?;
+        Ok(template.source)
+    }
+
+    pub fn license_template(&self, key: &str) -> Result<String> {
+        let license: LicenseTemplate = self.get(&format!("/licenses/{}", escape(key)))?;
+        Ok(license.body)
+    }
+
+    pub fn list_templates(&self) -> Result<Vec<String>> {
+        let path = "/user/repos?affiliation=owner,organization_member&per_page=100";
+        let repos: Vec<Repository> = self.get_all(path)?;
+        Ok(repos.into_iter().filter(|r| r.is_template).map(|r| r.full_name).collect())
+    }
+
+    pub fn add_collaborator(&self, repo: &str, user: &str, permission: &str) -> Result<()> {
+        let path = format!("/repos/{}/collaborators/{}", repo, escape(user));
+        self.put(&path, &PermissionRequest { permission })
+    }
+
+    pub fn add_team(&self, org: &str, slug: &str, repo: &str, permission: &str) -> Result<()> {
+        let path = format!("/orgs/{}/teams/{}/repos/{}", escape(org), escape(slug), repo);
+        self.put(&path, &PermissionRequest { permission })
+    }
+
+    pub fn add_webhook(&self,
+                       repo: &str,
+                       url: &str,
+                       content_type: &str,
+                       events: &[String],
+                       secret: Option<&Secret>)
+                       -> Result<()> {
+        let path = format!("/repos/{}/hooks", repo);
+        let request = HookRequest {
+            name: "web",
+            active: true,
+            events,
+            config: HookConfig {
+                url,
+                content_type,
+                secret,
+            },
+        };
+        let _: ::serde_json::Value = self.post(&path, &request)?;
+        Ok(())
+    }
+
+    pub fn add_deploy_key(&self, repo: &str, title: &str, key: &str, read_only: bool) -> Result<()> {
+        let path = format!("/repos/{}/keys", repo);
+        let request = DeployKeyRequest {
+            title,
+            key,
+            read_only,
+        };
+        let _: ::serde_json::Value = self.post(&path, &request)?;
+        Ok(())
+    }
+
+    pub fn actions_public_key(&self, repo: &str) -> Result<ActionsPublicKey> {
+        self.get(&format!("/repos/{}/actions/secrets/public-key", repo))
+    }
+
+    pub fn set_actions_secret(&self,
+                              repo: &str,
+                              public_key: &ActionsPublicKey,
+                              name: &str,
+                              value: &Secret)
+                              -> Result<()> {
+        let key = base64::decode(&*public_key.key)?;
+        let key = box_::PublicKey::from_slice(&key).ok_or(Error::InvalidPublicKey)?;
+        let encrypted = base64::encode(&*sealedbox::seal(value.expose().as_bytes(), &key));
+
+        let path = format!("/repos/{}/actions/secrets/{}", repo, escape(name));
+        let request = ActionsSecretRequest {
+            encrypted_value: &encrypted,
+            key_id: &public_key.key_id,
+        };
+        self.put(&path, &request)
+    }
+
+    pub fn set_actions_variable(&self, repo: &str, name: &str, value: &str) -> Result<()> {
+        let path = format!("/repos/{}/actions/variables", repo);
+        let request = ActionsVariableRequest {
+            name,
+            value,
+        };
+        let _: ::serde_json::Value = self.post(&path, &request)?;
+        Ok(())
+    }
+}
+
+fn prompt_otp() -> Result<Secret> {
+    print!("Two-factor authentication code: ");
+    io::stdout().flush()?;
+    let mut code = String::new();
+    io::stdin().read_line(&mut code)?;
+    let code = code.trim();
+    if code.is_empty() {
+        Err(Error::OtpRequired)
+    } else {
+        Ok(Secret::new(code))
+    }
+}
+
+fn missing_scope(scopes: &[String], request: &CreateRequest) -> Option<&'static str> {
+    let has = |names: &[&str]| names.iter().any(|name| scopes.iter().any(|s| s == name));
+
+    if request.private && !has(&["repo"]) {
+        Some("repo")
+    } else if !has(&["repo", "public_repo"]) {
+        Some("public_repo")
+    } else if request.rollback && !has(&["delete_repo"]) {
+        Some("delete_repo")
+    } else if !request.organization.is_empty() && !request.teams.is_empty() &&
+              !has(&["admin:org", "write:org", "read:org"]) {
+        Some("read:org")
+    } else {
+        None
+    }
+}
+
+fn next_link(link: &str) -> Option<&str> {
+    link.split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(|x| x.trim());
+            let url = parts.next().unwrap_or("");
+            if url.starts_with('<') && url.ends_with('>') &&
+               parts.any(|x| x == "rel=\"next\"" || x == "rel=next") {
+                Some(&url[1..url.len() - 1])
+            } else {
+                None
+            }
+        })
+        .next()
+}
+
+pub fn api_root(forge_url: Option<&str>) -> String {
+    match forge_url.map(|url| url.trim_end_matches('/')) {
+        None | Some("https://github.com") | Some("https://api.github.com") => API_ROOT.into(),
+        Some(url) if url.ends_with("/api/v3") => url.into(),
+        Some(url) => format!("{}/api/v3", url),
+    }
+}
+
+pub fn escape(segment: &str) -> String {
+    utf8_percent_encode(segment, PATH_SEGMENT_ENCODE_SET).to_string()
+}
+
+impl Forge for GitHubClient {
+    fn create_repository(&self, request: &CreateRequest) -> Result<RemoteRepository> {
+        self.create_repo(request).map(|r| r.into())
+    }
+
+    fn repository(&self, repo: &str) -> Result<RemoteRepository> {
+        self.get_repo(repo).map(|r| r.into())
+    }
+
+    fn delete_repository(&self, repo: &str) -> Result<()> {
+        self.delete_repo(repo)
+    }
+
+    fn update_settings(&self, repo: &str, settings: &RepoSettings) -> Result<()> {
+        self.patch_repo(repo, settings)?;
+        Ok(())
+    }
+
+    fn apply_settings(&self,
+                      request: &CreateRequest,
+                      resolved: &ResolvedParams,
+                      full_name: &str,
+                      key: Option<(&str, bool)>,
+                      summary: &mut Summary) {
+        GitHubClient::apply_settings(self, request, resolved, full_name, key, summary)
+    }
+
+    fn fork_repository(&self,
+                       repo: &str,
+                       organization: Option<&str>,
+                       name: Option<&str>)
+                       -> Result<ForkedRepository> {
+        let fork = self.fork_repo(repo, organization, name)?;
+        self.wait_for_contents(&fork.full_name)?;
+        Ok(ForkedRepository {
+            parent: fork.parent.clone().map(|parent| (*parent).into()),
+            repository: fork.into(),
+        })
+    }
+
+    fn set_otp(&self, otp: Secret) -> Result<()> {
+        GitHubClient::set_otp(self, otp);
+        Ok(())
+    }
+
+    fn token_info(&self) -> Result<TokenInfo> {
+        GitHubClient::token_info(self)
+    }
+
+    fn check_scopes(&self, request: &CreateRequest) -> Result<()> {
+        GitHubClient::check_scopes(self, request)
+    }
+
+    fn list_templates(&self) -> Result<Vec<String>> {
+        GitHubClient::list_templates(self)
+    }
+
+    fn gitignore_template(&self, name: &str) -> Result<String> {
+        GitHubClient::gitignore_template(self, name)
+    }
+
+    fn license_template(&self, key: &str) -> Result<String> {
+        GitHubClient::license_template(self, key)
+    }
+}
+
+impl From<Repository> for RemoteRepository {
+    fn from(repo: Repository) -> RemoteRepository {
+        RemoteRepository {
+            full_name: repo.full_name,
+            clone_url: repo.clone_url,
+            ssh_url: repo.ssh_url,
+            web_url: repo.html_url,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use manifest::Team;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    fn request(private: bool, organization: &str, teams: &[&str]) -> CreateRequest {
+        CreateRequest {
+            private,
+            organization: organization.into(),
+            teams: teams.iter()
+                .map(|team| {
+                    Team {
+                        slug: team.to_string(),
+                        permission: "push".into(),
+                    }
+                })
+                .collect(),
+            ..CreateRequest::default()
+        }
+    }
+
+    fn scopes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn missing_scopes() {
+        let public = request(false, "", &[]);
+        let private = request(true, "", &[]);
+        let org_teams = request(false, "org", &["devs"]);
+        let org = request(false, "org", &[]);
+
+        assert_eq!(missing_scope(&scopes(&[]), &public), Some("public_repo"));
+        assert_eq!(missing_scope(&scopes(&["public_repo"]), &public), None);
+        assert_eq!(missing_scope(&scopes(&["repo"]), &public), None);
+
+        assert_eq!(missing_scope(&scopes(&["public_repo"]), &private), Some("repo"));
+        assert_eq!(missing_scope(&scopes(&["repo"]), &private), None);
+
+        assert_eq!(missing_scope(&scopes(&["public_repo"]), &org), None);
+        assert_eq!(missing_scope(&scopes(&["public_repo"]), &org_teams), Some("read:org"));
+        assert_eq!(missing_scope(&scopes(&["repo", "read:org"]), &org_teams), None);
+        assert_eq!(missing_scope(&scopes(&["repo", "admin:org"]), &org_teams), None);
+        assert_eq!(missing_scope(&scopes(&["read:org"]), &org_teams), Some("public_repo"));
+
+        let rollback = CreateRequest { rollback: true, ..request(true, "", &[]) };
+        assert_eq!(missing_scope(&scopes(&["repo"]), &rollback), Some("delete_repo"));
+        assert_eq!(missing_scope(&scopes(&["repo", "delete_repo"]), &rollback), None);
+    }
+
+    fn serve_once(response: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf);
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn installation_tokens_skip_scopes() {
+        let options = HttpOptions { retries: 0, ..HttpOptions::default() };
+        let rollback = CreateRequest { rollback: true, ..request(true, "org", &["devs"]) };
+
+        // Never reaches the server, which would refuse the connection
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = GitHubClient::with_url(Some(&*url), "ghs_installation", &options).unwrap();
+        client.check_scopes(&rollback).unwrap();
+
+        let url = serve_once("HTTP/1.1 403 Forbidden\r\nContent-Type: application/json\r\n\
+                              Content-Length: 52\r\nConnection: close\r\n\r\n\
+                              {\"message\":\"Resource not accessible by integration\"}");
+        let client = GitHubClient::with_url(Some(&*url), "v1.token", &options).unwrap();
+        client.check_scopes(&rollback).unwrap();
+    }
+
+    #[test]
+    fn link_next_page() {
+        let link = "<https://api.github.com/repositories/1/labels?per_page=100&page=2>; \
+                    rel=\"next\", <https://api.github.com/repositories/1/labels?per_page=100&page=3>; \
+                    rel=\"last\"";
+        assert_eq!(next_link(link),
+                   Some("https://api.github.com/repositories/1/labels?per_page=100&page=2"));
+
+        let link = "<https://api.github.com/user/repos?page=1>; rel=\"first\", \
+                    <https://api.github.com/user/repos?page=2>; rel=\"prev\"";
+        assert_eq!(next_link(link), None);
+        assert_eq!(next_link(""), None);
+    }
+
+    #[test]
+    fn escaped_segments() {
+        assert_eq!(escape("octocat"), "octocat");
+        assert_eq!(escape("../orgs/evil"), "..%2Forgs%2Fevil");
+        assert_eq!(escape("team name?"), "team%20name%3F");
+    }
+
+    #[test]
+    fn enterprise_api_root() {
+        assert_eq!(api_root(None), API_ROOT);
+        assert_eq!(api_root(Some("https://github.com/")), API_ROOT);
+        assert_eq!(api_root(Some("https://ghe.example.com")), "https://ghe.example.com/api/v3");
+        assert_eq!(api_root(Some("https://ghe.example.com/api/v3/")),
+                   "https://ghe.example.com/api/v3");
+    }
+}
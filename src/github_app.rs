@@ -0,0 +1,152 @@
+use error::Result;
+use github::USER_AGENT;
+use http::{Client, HttpOptions, Method};
+use secret::Secret;
+
+use base64;
+use hyper::header::{Authorization, UserAgent};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde_json as json;
+
+use std::fs::File;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const JWT_LIFETIME: u64 = 540;
+
+#[derive(Serialize, Debug)]
+struct JwtHeader<'a> {
+    alg: &'a str,
+    typ: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct JwtClaims<'a> {
+    iat: u64,
+    exp: u64,
+    iss: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct InstallationToken {
+    token: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitHubApp {
+    pub app_id: String,
+    pub key: String,
+    pub installation_id: String,
+}
+
+fn encode_segment(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE).trim_end_matches('=').to_string()
+}
+
+fn jwt_at(pem: &[u8], app_id: &str, now: u64) -> Result<String> {
+    let key = PKey::private_key_from_pem(pem)?;
+    let header = JwtHeader {
+        alg: "RS256",
+        typ: "JWT",
+    };
+    let claims = JwtClaims {
+        // Backdated in case the local clock is ahead of GitHub's
+        iat: now.saturating_sub(60),
+        exp: now + JWT_LIFETIME,
+        iss: app_id,
+    };
+    let message = format!("{}.{}",
+                          encode_segment(&json::to_vec(&header)?),
+                          encode_segment(&json::to_vec(&claims)?));
+
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(message.as_bytes())?;
+    let signature = signer.sign_to_vec()?;
+    Ok(format!("{}.{}", message, encode_segment(&signature)))
+}
+
+impl GitHubApp {
+    pub fn new<S>(app_id: S, key: S, installation_id: S) -> GitHubApp
+        where S: Into<String>
+    {
+        GitHubApp {
+            app_id: app_id.into(),
+            key: key.into(),
+            installation_id: installation_id.into(),
+        }
+    }
+
+    fn jwt(&self) -> Result<String> {
+        let mut pem = Vec::new();
+        File::open(&*self.key)?.read_to_end(&mut pem)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        jwt_at(&pem, &self.app_id, now)
+    }
+
+    pub fn installation_token(&self, api_root: &str, options: &HttpOptions) -> Result<Secret> {
+        let client = Client::new(api_root, options)?;
+        let url = format!("{}/app/installations/{}/access_tokens",
+                          api_root,
+                          self.installation_id);
+        let request = client.request(Method::Post, url)
+            .header(Authorization(format!("Bearer {}", self.jwt()?)))
+            .header(UserAgent(USER_AGENT.to_string()))
+            .raw_header("Accept", "application/vnd.github.machine-man-preview+json".into());
+        let token: InstallationToken = client.send(&request)?.json()?;
+        info!("Using an installation token for GitHub App {}", self.app_id);
+        Ok(Secret::new(token.token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::sign::Verifier;
+    use serde_json::{self as json, Value};
+
+    fn decode_segment(segment: &str) -> Vec<u8> {
+        let padding = (4 - segment.len() % 4) % 4;
+        let padded = format!("{}{}", segment, "=".repeat(padding));
+        base64::decode_config(&*padded, base64::URL_SAFE).unwrap()
+    }
+
+    #[test]
+    fn jwt_header_claims_and_signature() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let pem = rsa.private_key_to_pem().unwrap();
+        let public = PKey::public_key_from_pem(&rsa.public_key_to_pem().unwrap()).unwrap();
+
+        let jwt = jwt_at(&pem, "12345", 1000).unwrap();
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        assert!(parts.iter().all(|part| !part.contains('=')));
+
+        let header: Value = json::from_slice(&decode_segment(parts[0])).unwrap();
+        assert_eq!(header["alg"], "RS256");
+        assert_eq!(header["typ"], "JWT");
+
+        let claims: Value = json::from_slice(&decode_segment(parts[1])).unwrap();
+        assert_eq!(claims["iat"], 940);
+        assert_eq!(claims["exp"], 1000 + JWT_LIFETIME);
+        assert_eq!(claims["iss"], "12345");
+
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &public).unwrap();
+        verifier.update(format!("{}.{}", parts[0], parts[1]).as_bytes()).unwrap();
+        assert!(verifier.verify(&decode_segment(parts[2])).unwrap());
+    }
+
+    #[test]
+    fn jwt_issued_at_does_not_underflow() {
+        let pem = Rsa::generate(2048).unwrap().private_key_to_pem().unwrap();
+        let jwt = jwt_at(&pem, "1", 30).unwrap();
+        let claims: Value =
+            json::from_slice(&decode_segment(jwt.split('.').nth(1).unwrap())).unwrap();
+        assert_eq!(claims["iat"], 0);
+    }
+}
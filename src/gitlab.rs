@@ -0,0 +1,185 @@
+use error::{Error, Result};
+use forge::{Forge, RemoteRepository};
+use github::{self, RepoSettings, USER_AGENT};
+use http::{Client, HttpOptions, Method, Request};
+use manifest::CreateRequest;
+use secret::Secret;
+
+use hyper::header::UserAgent;
+use serde::{Serialize, Deserialize};
+
+const DEFAULT_URL: &str = "https://gitlab.com";
+
+#[derive(Deserialize, Debug)]
+struct Namespace {
+    id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct Project {
+    path_with_namespace: String,
+    http_url_to_repo: String,
+    ssh_url_to_repo: String,
+    web_url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Template {
+    content: String,
+}
+
+#[derive(Serialize, Debug)]
+struct CreateProject<'a> {
+    name: &'a str,
+    path: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace_id: Option<u64>,
+    description: &'a str,
+    visibility: &'a str,
+    issues_enabled: bool,
+    wiki_enabled: bool,
+    initialize_with_readme: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct UpdateProject<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visibility: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    issues_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wiki_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_branch: Option<&'a str>,
+}
+
+fn visibility(private: bool) -> &'static str {
+    if private { "private" } else { "public" }
+}
+
+pub struct GitLabClient {
+    client: Client,
+    url: String,
+    token: Secret,
+}
+
+impl GitLabClient {
+    pub fn new<S>(url: Option<&str>, token: S, options: &HttpOptions) -> Result<GitLabClient>
+        where S: Into<Secret>
+    {
+        let url = url.unwrap_or(DEFAULT_URL).trim_end_matches('/');
+        Ok(GitLabClient {
+            client: Client::new(url, options)?,
+            url: url.to_string(),
+            token: token.into(),
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> Request {
+        self.client
+            .request(method, format!("{}/api/v4{}", self.url, path))
+            .raw_header("PRIVATE-TOKEN", self.token.expose().to_string())
+            .header(UserAgent(USER_AGENT.to_string()))
+    }
+
+    fn get<T>(&self, path: &str) -> Result<T>
+        where T: Deserialize
+    {
+        let mut res = self.client.send(&self.request(Method::Get, path))?;
+        res.json()
+    }
+
+    fn send_json<B, T>(&self, method: Method, path: &str, body: &B) -> Result<T>
+        where B: Serialize,
+              T: Deserialize
+    {
+        let mut res = self.client.send(&self.request(method, path).json(body)?)?;
+        res.json()
+    }
+
+    fn namespace_id(&self, namespace: &str) -> Result<u64> {
+        let namespace: Namespace = self.get(&format!("/namespaces/{}",
+                                                           github::escape(namespace)))?;
+        Ok(namespace.id)
+    }
+
+    fn project_path(repo: &str) -> String {
+        format!("/projects/{}", github::escape(repo))
+    }
+}
+
+impl From<Project> for RemoteRepository {
+    fn from(project: Project) -> RemoteRepository {
+        RemoteRepository {
+            full_name: project.path_with_namespace,
+            clone_url: project.http_url_to_repo,
+            ssh_url: project.ssh_url_to_repo,
+            web_url: project.web_url,
+        }
+    }
+}
+
+impl Forge for GitLabClient {
+    fn create_repository(&self, request: &CreateRequest) -> Result<RemoteRepository> {
+        if !request.template.is_empty() {
+            return Err(Error::Unsupported("template".into()));
+        }
+
+        let namespace_id = if request.organization.is_empty() {
+            None
+        } else {
+            Some(self.namespace_id(&request.organization)?)
+        };
+        let project = CreateProject {
+            name: &request.name,
+            path: &request.name,
+            namespace_id,
+            description: &request.description,
+            visibility: visibility(request.private),
+            issues_enabled: request.has_issues,
+            wiki_enabled: request.has_wiki,
+            initialize_with_readme: request.auto_init,
+        };
+        let project: Project = self.send_json(Method::Post, "/projects", &project)?;
+        Ok(project.into())
+    }
+
+    fn repository(&self, repo: &str) -> Result<RemoteRepository> {
+        let project: Project = self.get(&GitLabClient::project_path(repo))?;
+        Ok(project.into())
+    }
+
+    fn delete_repository(&self, repo: &str) -> Result<()> {
+        self.client.send(&self.request(Method::Delete, &GitLabClient::project_path(repo)))?;
+        Ok(())
+    }
+
+    fn update_settings(&self, repo: &str, settings: &RepoSettings) -> Result<()> {
+        let update = UpdateProject {
+            name: settings.name.as_deref(),
+            description: settings.description.as_deref(),
+            visibility: settings.private.map(visibility),
+            issues_enabled: settings.has_issues,
+            wiki_enabled: settings.has_wiki,
+            default_branch: settings.default_branch.as_deref(),
+        };
+        let _: Project = self.send_json(Method::Put, &GitLabClient::project_path(repo), &update)?;
+        Ok(())
+    }
+
+    fn gitignore_template(&self, name: &str) -> Result<String> {
+        let template: Template =
+            self.get(&format!("/templates/gitignores/{}", github::escape(name)))?;
+        Ok(template.content)
+    }
+
+    fn license_template(&self, key: &str) -> Result<String> {
+        let template: Template =
+            self.get(&format!("/templates/licenses/{}", github::escape(key)))?;
+        Ok(template.content)
+    }
+}
@@ -0,0 +1,441 @@
+use error::{Error, Result};
+use git;
+use secret::scrub;
+
+use hyper;
+use hyper::client::{Pool, ProxyConfig};
+use hyper::header::{ContentType, Header, HeaderFormat, Headers};
+use hyper::net::{HttpConnector, HttpsConnector};
+use hyper_native_tls::NativeTlsClient;
+use native_tls::{Certificate, TlsConnector};
+use serde::{Serialize, Deserialize};
+use serde_json as json;
+use base64;
+use url::Url;
+
+pub use hyper::method::Method;
+pub use hyper::status::StatusCode;
+
+use std::cmp;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::str;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAX_BACKOFF: u64 = 60;
+
+#[derive(Deserialize, Debug)]
+struct ApiError {
+    message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpOptions {
+    pub timeout: Duration,
+    pub retries: u32,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub ca_info: Option<String>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> HttpOptions {
+        HttpOptions {
+            timeout: Duration::from_secs(30),
+            retries: 3,
+            proxy: None,
+            no_proxy: None,
+            ca_info: None,
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name)
+        .or_else(|_| env::var(name.to_lowercase()))
+        .ok()
+        .and_then(|x| if x.is_empty() { None } else { Some(x) })
+}
+
+impl HttpOptions {
+    pub fn from_env() -> HttpOptions {
+        HttpOptions {
+            proxy: env_var("HTTPS_PROXY").or_else(|| git::get_config_value("http.proxy").ok()),
+            no_proxy: env_var("NO_PROXY"),
+            ca_info: env_var("GIT_SSL_CAINFO")
+                .or_else(|| git::get_config_value("http.sslCAInfo").ok()),
+            ..HttpOptions::default()
+        }
+    }
+
+    pub fn proxy_for(&self, url: &str) -> Option<String> {
+        let proxy = match self.proxy {
+            Some(ref proxy) => proxy,
+            None => return None,
+        };
+        let url = Url::parse(url).ok();
+        let host = url.as_ref().and_then(|u| u.host_str()).unwrap_or("");
+        let excluded = self.no_proxy.as_ref().is_some_and(|no_proxy| {
+            no_proxy.split(',').map(|x| x.trim().trim_start_matches('.')).any(|x| {
+                x == "*" || (!x.is_empty() && (host == x || host.ends_with(&*format!(".{}", x))))
+            })
+        });
+
+        if excluded {
+            None
+        } else if proxy.contains("://") {
+            Some(proxy.clone())
+        } else {
+            Some(format!("http://{}", proxy))
+        }
+    }
+}
+
+fn read_certificates(path: &str) -> Result<Vec<Certificate>> {
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+
+    let mut certificates = Vec::new();
+    let mut block: Option<String> = None;
+    for line in text.lines().map(|x| x.trim()) {
+        if line == "-----BEGIN CERTIFICATE-----" {
+            block = Some(String::new());
+        } else if line == "-----END CERTIFICATE-----" {
+            if let Some(data) = block.take() {
+                let der = base64::decode(&*data)?;
+                certificates.push(Certificate::from_der(&der)?);
+            }
+        } else if let Some(ref mut data) = block {
+            data.push_str(line);
+        }
+    }
+
+    Ok(certificates)
+}
+
+fn tls_client(ca_info: Option<&str>) -> Result<NativeTlsClient> {
+    let mut builder = TlsConnector::builder();
+    if let Some(path) = ca_info {
+        for certificate in read_certificates(path)? {
+            builder.add_root_certificate(certificate);
+        }
+    }
+    Ok(NativeTlsClient::from(builder.build()?))
+}
+
+#[derive(Clone)]
+pub struct Request {
+    method: Method,
+    url: String,
+    headers: Headers,
+    body: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Request {{ method: {}, url: {} }}", self.method, scrub(&self.url))
+    }
+}
+
+impl Request {
+    pub fn header<H>(mut self, header: H) -> Request
+        where H: Header + HeaderFormat
+    {
+        self.headers.set(header);
+        self
+    }
+
+    pub fn raw_header(mut self, name: &'static str, value: String) -> Request {
+        self.headers.set_raw(name, vec![value.into_bytes()]);
+        self
+    }
+
+    pub fn json<B>(mut self, body: &B) -> Result<Request>
+        where B: Serialize
+    {
+        self.body = Some(json::to_vec(body)?);
+        self.headers.set(ContentType::json());
+        Ok(self)
+    }
+}
+
+pub struct Response {
+    inner: hyper::client::Response,
+}
+
+impl Response {
+    pub fn status(&self) -> StatusCode {
+        self.inner.status
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.inner.headers
+    }
+
+    pub fn header_str(&self, name: &str) -> Option<&str> {
+        raw_header_str(self.headers(), name)
+    }
+
+    pub fn json<T>(&mut self) -> Result<T>
+        where T: Deserialize
+    {
+        json::from_reader(&mut self.inner).map_err(|e| e.into())
+    }
+}
+
+fn raw_header_str<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers.get_raw(name)
+        .and_then(|values| values.first())
+        .and_then(|value| str::from_utf8(value).ok())
+        .map(|value| value.trim())
+}
+
+fn raw_header_u64(headers: &Headers, name: &str) -> Option<u64> {
+    raw_header_str(headers, name).and_then(|value| value.parse().ok())
+}
+
+pub fn header_u64(res: &Response, name: &str) -> Option<u64> {
+    raw_header_u64(res.headers(), name)
+}
+
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(cmp::min(1 << cmp::min(attempt, 6), MAX_BACKOFF))
+}
+
+fn rate_limit_delay(headers: &Headers, now: u64) -> Option<Duration> {
+    if let Some(seconds) = raw_header_u64(headers, "Retry-After") {
+        return Some(Duration::from_secs(seconds));
+    }
+    if raw_header_u64(headers, "X-RateLimit-Remaining") != Some(0) {
+        return None;
+    }
+    raw_header_u64(headers, "X-RateLimit-Reset")
+        .map(|reset| Duration::from_secs(reset.saturating_sub(now) + 1))
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(*method,
+             Method::Get | Method::Head | Method::Options | Method::Put | Method::Delete |
+             Method::Patch)
+}
+
+// The request never left the machine, so it is safe to send again whatever the method
+fn is_connect_error(e: &Error) -> bool {
+    match *e {
+        Error::Http(hyper::Error::Io(ref e)) => {
+            matches!(e.kind(),
+                     io::ErrorKind::ConnectionRefused | io::ErrorKind::AddrNotAvailable)
+        }
+        _ => false,
+    }
+}
+
+fn retry_delay(method: &Method,
+               status: StatusCode,
+               headers: &Headers,
+               attempt: u32,
+               now: u64)
+               -> Option<Duration> {
+    match status {
+        StatusCode::Forbidden => rate_limit_delay(headers, now),
+        StatusCode::TooManyRequests => {
+            rate_limit_delay(headers, now).or_else(|| Some(backoff(attempt)))
+        }
+        status if status.is_server_error() && is_idempotent(method) => Some(backoff(attempt)),
+        _ => None,
+    }
+}
+
+fn api_error(mut res: Response) -> Error {
+    let status = res.status();
+    let otp = res.headers()
+        .get_raw("X-GitHub-OTP")
+        .and_then(|values| values.first())
+        .is_some_and(|value| value.starts_with(b"required"));
+    if status == StatusCode::Unauthorized && otp {
+        return Error::OtpRequired;
+    }
+
+    let message = res.json::<ApiError>()
+        .map(|e| e.message)
+        .unwrap_or_else(|_| status.to_string());
+    Error::Api(status.to_u16(), message)
+}
+
+pub struct Client {
+    client: hyper::Client,
+    options: HttpOptions,
+}
+
+impl Client {
+    pub fn new(base_url: &str, options: &HttpOptions) -> Result<Client> {
+        let ssl = tls_client(options.ca_info.as_deref())?;
+        let mut client = match options.proxy_for(base_url) {
+            Some(proxy) => {
+                let proxy = Url::parse(&proxy)?;
+                let host = proxy.host_str()
+                        .ok_or(Error::InvalidProxy(scrub(proxy.as_str())))?
+                    .to_string();
+                let port = proxy.port_or_known_default().unwrap_or(80);
+                debug!("Using proxy {}:{} for {}", host, port, base_url);
+                let config = ProxyConfig::new(proxy.scheme(), host, port, HttpConnector, ssl);
+                hyper::Client::with_proxy_config(config)
+            }
+            None => {
+                let connector = HttpsConnector::new(ssl);
+                hyper::Client::with_connector(Pool::with_connector(Default::default(), connector))
+            }
+        };
+        client.set_read_timeout(Some(options.timeout));
+        client.set_write_timeout(Some(options.timeout));
+
+        Ok(Client {
+            client,
+            options: options.clone(),
+        })
+    }
+
+    pub fn request<S>(&self, method: Method, url: S) -> Request
+        where S: Into<String>
+    {
+        Request {
+            method,
+            url: url.into(),
+            headers: Headers::new(),
+            body: None,
+        }
+    }
+
+    fn send_once(&self, request: &Request) -> Result<Response> {
+        let mut builder = self.client
+            .request(request.method.clone(), &*request.url)
+            .headers(request.headers.clone());
+        if let Some(ref body) = request.body {
+            builder = builder.body(&body[..]);
+        }
+        let res = builder.send()?;
+        Ok(Response { inner: res })
+    }
+
+    pub fn send(&self, request: &Request) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            let delay = match self.send_once(request) {
+                Ok(res) => {
+                    if res.status().is_success() {
+                        return Ok(res);
+                    }
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0);
+                    match retry_delay(&request.method, res.status(), res.headers(), attempt, now) {
+                        Some(delay) if attempt < self.options.retries => {
+                            warn!("Request failed with {}, retrying in {}s",
+                                  res.status(),
+                                  delay.as_secs());
+                            delay
+                        }
+                        _ => return Err(api_error(res)),
+                    }
+                }
+                Err(e) => {
+                    let transient = match e {
+                        Error::Http(_) => is_idempotent(&request.method) || is_connect_error(&e),
+                        _ => false,
+                    };
+                    if !transient || attempt >= self.options.retries {
+                        return Err(e);
+                    }
+                    let delay = backoff(attempt);
+                    warn!("Request failed: {}, retrying in {}s",
+                          scrub(&e.to_string()),
+                          delay.as_secs());
+                    delay
+                }
+            };
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::Headers;
+    use std::time::Duration;
+
+    fn headers(values: &[(&'static str, &str)]) -> Headers {
+        let mut headers = Headers::new();
+        for &(name, value) in values {
+            headers.set_raw(name, vec![value.as_bytes().to_vec()]);
+        }
+        headers
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(1), Duration::from_secs(2));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+        assert_eq!(backoff(6), Duration::from_secs(MAX_BACKOFF));
+        assert_eq!(backoff(40), Duration::from_secs(MAX_BACKOFF));
+    }
+
+    #[test]
+    fn retry_server_errors_only_for_idempotent_methods() {
+        let none = Headers::new();
+        let error = StatusCode::BadGateway;
+        assert_eq!(retry_delay(&Method::Get, error, &none, 2, 0),
+                   Some(Duration::from_secs(4)));
+        assert_eq!(retry_delay(&Method::Put, error, &none, 0, 0),
+                   Some(Duration::from_secs(1)));
+        assert_eq!(retry_delay(&Method::Delete, error, &none, 0, 0),
+                   Some(Duration::from_secs(1)));
+        assert_eq!(retry_delay(&Method::Patch, error, &none, 0, 0),
+                   Some(Duration::from_secs(1)));
+        assert_eq!(retry_delay(&Method::Post, error, &none, 0, 0), None);
+        assert_eq!(retry_delay(&Method::Get, StatusCode::NotFound, &none, 0, 0), None);
+    }
+
+    #[test]
+    fn retry_rate_limits_for_every_method() {
+        let none = Headers::new();
+        assert_eq!(retry_delay(&Method::Post, StatusCode::TooManyRequests, &none, 1, 0),
+                   Some(Duration::from_secs(2)));
+
+        let retry_after = headers(&[("Retry-After", "7")]);
+        assert_eq!(retry_delay(&Method::Post, StatusCode::TooManyRequests, &retry_after, 0, 0),
+                   Some(Duration::from_secs(7)));
+        assert_eq!(retry_delay(&Method::Post, StatusCode::Forbidden, &retry_after, 0, 0),
+                   Some(Duration::from_secs(7)));
+
+        let exhausted = headers(&[("X-RateLimit-Remaining", "0"),
+                                  ("X-RateLimit-Reset", "1030")]);
+        assert_eq!(retry_delay(&Method::Post, StatusCode::Forbidden, &exhausted, 0, 1000),
+                   Some(Duration::from_secs(31)));
+        assert_eq!(retry_delay(&Method::Get, StatusCode::Forbidden, &exhausted, 0, 2000),
+                   Some(Duration::from_secs(1)));
+
+        let remaining = headers(&[("X-RateLimit-Remaining", "12"),
+                                  ("X-RateLimit-Reset", "1030")]);
+        assert_eq!(retry_delay(&Method::Get, StatusCode::Forbidden, &remaining, 0, 1000), None);
+        assert_eq!(retry_delay(&Method::Get, StatusCode::Forbidden, &none, 0, 1000), None);
+    }
+
+    #[test]
+    fn retry_post_only_before_connecting() {
+        use std::io;
+        let refused = Error::Http(hyper::Error::Io(io::Error::new(io::ErrorKind::ConnectionRefused,
+                                                                  "refused")));
+        let reset = Error::Http(hyper::Error::Io(io::Error::new(io::ErrorKind::ConnectionReset,
+                                                                "reset")));
+        assert!(is_connect_error(&refused));
+        assert!(!is_connect_error(&reset));
+        assert!(!is_connect_error(&Error::Api(500, "error".into())));
+    }
+}
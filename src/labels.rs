@@ -0,0 +1,180 @@
+use error::{Error, Result};
+use github::{self, GitHubClient};
+use summary::Summary;
+
+use serde_json as json;
+use toml;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Label {
+    pub name: String,
+    pub color: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub from: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LabelFile {
+    labels: Vec<Label>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExistingLabel {
+    name: String,
+    color: String,
+    description: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct CreateLabel<'a> {
+    name: &'a str,
+    color: &'a str,
+    description: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct UpdateLabel<'a> {
+    new_name: &'a str,
+    color: &'a str,
+    description: &'a str,
+}
+
+pub fn load(value: &json::Value) -> Result<Vec<Label>> {
+    match *value {
+        json::Value::String(ref path) => load_file(path),
+        _ => from_json(value.clone()),
+    }
+}
+
+// Both a bare list and the { "labels": [...] } shape of the TOML files work
+fn from_json(value: json::Value) -> Result<Vec<Label>> {
+    match value {
+        json::Value::Array(_) => json::from_value(value).map_err(|e| e.into()),
+        json::Value::Object(_) => {
+            let file: LabelFile = json::from_value(value)?;
+            Ok(file.labels)
+        }
+        _ => Err(Error::InvalidManifest("labels".into())),
+    }
+}
+
+fn parse(text: &str, extension: Option<&str>) -> Result<Vec<Label>> {
+    match extension {
+        Some("toml") => {
+            let file: LabelFile = toml::from_str(text)?;
+            Ok(file.labels)
+        }
+        _ => from_json(json::from_str(text)?),
+    }
+}
+
+fn load_file(path: &str) -> Result<Vec<Label>> {
+    let mut file = File::open(path)?;
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    parse(&text, Path::new(path).extension().and_then(|x| x.to_str()))
+}
+
+fn trim_color(color: &str) -> &str {
+    color.trim_start_matches('#')
+}
+
+fn same_color(a: &str, b: &str) -> bool {
+    trim_color(a).eq_ignore_ascii_case(trim_color(b))
+}
+
+pub fn sync(client: &GitHubClient, repo: &str, labels: &[Label], summary: &mut Summary) {
+    let path = format!("/repos/{}/labels?per_page=100", repo);
+    let existing: Vec<ExistingLabel> = match summary.record("Fetch labels",
+                                                             client.get_all(&path)) {
+        Some(existing) => existing,
+        None => return,
+    };
+    let mut remaining: Vec<&ExistingLabel> = existing.iter().collect();
+
+    for label in labels {
+        let color = trim_color(&label.color);
+        let update = UpdateLabel {
+            new_name: &label.name,
+            color,
+            description: &label.description,
+        };
+
+        let current = remaining.iter()
+            .position(|l| l.name.to_lowercase() == label.name.to_lowercase())
+            .or_else(|| {
+                if label.from.is_empty() {
+                    None
+                } else {
+                    remaining.iter().position(|l| l.name.to_lowercase() == label.from.to_lowercase())
+                }
+            })
+            .map(|i| remaining.remove(i));
+
+        match current {
+            Some(current) => {
+                if current.name == label.name && same_color(&current.color, color) &&
+                   current.description.as_ref().map_or("", |d| &**d) == label.description {
+                    continue;
+                }
+                let step = if current.name == label.name {
+                    format!("Update label {}", label.name)
+                } else {
+                    format!("Rename label {} to {}", current.name, label.name)
+                };
+                let path = format!("/repos/{}/labels/{}", repo, github::escape(&current.name));
+                let result: Result<json::Value> = client.patch(&path, &update);
+                summary.record(step, result);
+            }
+            None => {
+                let step = format!("Create label {}", label.name);
+                let path = format!("/repos/{}/labels", repo);
+                let create = CreateLabel {
+                    name: &label.name,
+                    color,
+                    description: &label.description,
+                };
+                let result: Result<json::Value> = client.post(&path, &create);
+                summary.record(step, result);
+            }
+        }
+    }
+
+    for label in remaining {
+        let step = format!("Delete label {}", label.name);
+        let path = format!("/repos/{}/labels/{}", repo, github::escape(&label.name));
+        summary.record(step, client.delete(&path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_shapes() {
+        let bare = r#"[{ "name": "bug", "color": "d73a4a" }]"#;
+        let wrapped = r#"{ "labels": [{ "name": "bug", "color": "d73a4a" }] }"#;
+        let toml = "[[labels]]\nname = \"bug\"\ncolor = \"d73a4a\"\n";
+        let cases = [(bare, Some("json")), (wrapped, Some("json")), (toml, Some("toml"))];
+        for &(text, extension) in &cases {
+            let labels = parse(text, extension).unwrap();
+            assert_eq!(labels.len(), 1);
+            assert_eq!(labels[0].name, "bug");
+        }
+        assert!(parse("\"bug\"", None).is_err());
+    }
+
+    #[test]
+    fn colors_ignore_case_and_hash() {
+        assert!(same_color("FF0000", "ff0000"));
+        assert!(same_color("#ff0000", "FF0000"));
+        assert!(!same_color("ff0000", "ff0001"));
+    }
+}
@@ -0,0 +1,53 @@
+// serde_derive 0.9 wraps its generated impls in a named const, which newer
+// compilers report for every derive
+#![allow(non_local_definitions)]
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+extern crate serde_json;
+extern crate tempdir;
+extern crate hyper;
+extern crate hyper_native_tls;
+extern crate native_tls;
+extern crate openssl;
+extern crate git2;
+extern crate url;
+#[macro_use]
+extern crate nom;
+#[macro_use]
+extern crate log;
+extern crate notify;
+extern crate tempfile;
+extern crate toml;
+extern crate base64;
+extern crate sodiumoxide;
+extern crate time;
+extern crate atty;
+
+pub mod error;
+pub mod git;
+pub mod credentials;
+pub mod forge;
+pub mod gitea;
+pub mod github;
+pub mod github_app;
+pub mod gitlab;
+pub mod http;
+pub mod local;
+pub mod manifest;
+pub mod oauth;
+pub mod labels;
+pub mod deploy_key;
+pub mod secret;
+pub mod summary;
+pub mod templates;
+pub mod token_sources;
+
+pub use error::{Error, Result};
+pub use forge::{Forge, ForgeKind, RemoteRepository};
+pub use git::GitMode;
+pub use http::HttpOptions;
+pub use secret::Secret;
+pub use github::{GitHubClient, Repository, RepoSettings};
+pub use manifest::{CreateRequest, CreateRequestBuilder};
@@ -0,0 +1,214 @@
+use error::{Error, Result};
+use forge::{Forge, RemoteRepository};
+use github::RepoSettings;
+use manifest::CreateRequest;
+
+use git2::{Repository, Signature};
+use url::Url;
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub struct LocalForge {
+    root: PathBuf,
+}
+
+impl LocalForge {
+    pub fn new(url: Option<&str>) -> Result<LocalForge> {
+        let url = url.ok_or(Error::MissingParameter("forge-url".into()))?;
+        let url = Url::parse(url)?;
+        let root = url.to_file_path().map_err(|_| Error::InvalidTargetDir)?;
+        fs::create_dir_all(&root)?;
+        Ok(LocalForge { root })
+    }
+
+    fn repo_path(&self, repo: &str) -> Result<PathBuf> {
+        let repo = repo.trim_end_matches(".git");
+        let segments: Vec<&str> = repo.split('/').collect();
+        if segments.len() > 2 || !segments.iter().all(|x| valid_segment(x)) {
+            return Err(Error::InvalidValue(format!("repository name {}", repo)));
+        }
+        Ok(self.root.join(format!("{}.git", repo)))
+    }
+
+    fn remote_repository(&self, repo: &str) -> Result<RemoteRepository> {
+        let path = self.repo_path(repo)?;
+        let url = Url::from_file_path(&path).map_err(|_| Error::InvalidTargetDir)?;
+        Ok(RemoteRepository {
+            full_name: repo.trim_end_matches(".git").to_string(),
+            clone_url: url.to_string(),
+            ssh_url: path.to_string_lossy().into_owned(),
+            web_url: url.to_string(),
+        })
+    }
+}
+
+// Names stay inside the root, so each part must be a plain directory name
+fn valid_segment(segment: &str) -> bool {
+    !segment.is_empty() && segment != "." && segment != ".." && !segment.contains('\\') &&
+    !segment.contains(':')
+}
+
+fn write_description(path: &Path, description: &str) -> Result<()> {
+    let mut file = File::create(path.join("description"))?;
+    writeln!(file, "{}", description)?;
+    Ok(())
+}
+
+fn seed(repo: &Repository, request: &CreateRequest) -> Result<()> {
+    let mut readme = format!("# {}\n", request.name);
+    if !request.description.is_empty() {
+        readme.push_str(&format!("\n{}\n", request.description));
+    }
+
+    let mut tree = repo.treebuilder(None)?;
+    let blob = repo.blob(readme.as_bytes())?;
+    tree.insert("README.md", blob, 0o100644)?;
+
+    let tree = repo.find_tree(tree.write()?)?;
+    let signature = repo.signature()
+        .or_else(|_| Signature::now("create-gh-repo", "create-gh-repo@localhost"))?;
+    repo.commit(Some("HEAD"), &signature, &signature, "Initial commit", &tree, &[])?;
+    Ok(())
+}
+
+impl Forge for LocalForge {
+    fn create_repository(&self, request: &CreateRequest) -> Result<RemoteRepository> {
+        if !request.template.is_empty() {
+            return Err(Error::Unsupported("template".into()));
+        }
+        // There is no template catalogue to render these from
+        if !request.license_key().is_empty() {
+            return Err(Error::Unsupported("license templates".into()));
+        }
+        if !request.gitignore_names().is_empty() {
+            return Err(Error::Unsupported("gitignore templates".into()));
+        }
+
+        let full_name = if request.organization.is_empty() {
+            request.name.clone()
+        } else {
+            format!("{}/{}", request.organization, request.name)
+        };
+        let path = self.repo_path(&full_name)?;
+        if path.exists() {
+            return Err(Error::InvalidTargetDir);
+        }
+
+        let repo = Repository::init_bare(&path)?;
+        write_description(&path, &request.description)?;
+        if request.auto_init {
+            seed(&repo, request)?;
+        }
+
+        self.remote_repository(&full_name)
+    }
+
+    fn repository(&self, repo: &str) -> Result<RemoteRepository> {
+        Repository::open_bare(self.repo_path(repo)?)?;
+        self.remote_repository(repo)
+    }
+
+    fn delete_repository(&self, repo: &str) -> Result<()> {
+        let path = self.repo_path(repo)?;
+        Repository::open_bare(&path)?;
+        fs::remove_dir_all(path).map_err(|e| e.into())
+    }
+
+    fn update_settings(&self, repo: &str, settings: &RepoSettings) -> Result<()> {
+        let mut path = self.repo_path(repo)?;
+        let git_repo = Repository::open_bare(&path)?;
+
+        if let Some(ref branch) = settings.default_branch {
+            git_repo.set_head(&format!("refs/heads/{}", branch))?;
+        }
+        if let Some(ref description) = settings.description {
+            write_description(&path, description)?;
+        }
+        if let Some(ref name) = settings.name {
+            if !valid_segment(name) || name.contains('/') {
+                return Err(Error::InvalidValue(format!("repository name {}", name)));
+            }
+            let renamed = path.with_file_name(format!("{}.git", name));
+            fs::rename(&path, &renamed)?;
+            path = renamed;
+        }
+        debug!("Updated local repository: {}", path.display());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use forge::Forge;
+    use git;
+    use manifest::CreateRequestBuilder;
+    use tempdir::TempDir;
+    use url::Url;
+
+    #[test]
+    fn create_and_clone() {
+        let dir = TempDir::new("create-gh-repo-test").unwrap();
+        let root = Url::from_file_path(dir.path().join("remote")).unwrap();
+        let forge = LocalForge::new(Some(root.as_str())).unwrap();
+
+        let mut request = CreateRequestBuilder::new("project");
+        request.description("A test project");
+        let repo = forge.create_repository(&request.build().unwrap()).unwrap();
+        assert_eq!(repo.full_name, "project".to_string());
+
+        let clone_dir = dir.path().join("project");
+        let clone_dir = clone_dir.to_str().unwrap();
+        git::clone(&repo.clone_url, Some(clone_dir)).unwrap();
+        assert!(dir.path().join("project").join("README.md").exists());
+
+        forge.delete_repository("project").unwrap();
+        assert!(forge.repository("project").is_err());
+    }
+
+    #[test]
+    fn templates_are_unsupported() {
+        let dir = TempDir::new("create-gh-repo-test").unwrap();
+        let root = Url::from_file_path(dir.path().join("remote")).unwrap();
+        let forge = LocalForge::new(Some(root.as_str())).unwrap();
+
+        let mut request = CreateRequestBuilder::new("project");
+        request.license_template("mit");
+        match forge.create_repository(&request.build().unwrap()) {
+            Err(Error::Unsupported(_)) => {}
+            other => panic!("expected the license template to be rejected, got {:?}", other),
+        }
+
+        let mut request = CreateRequestBuilder::new("project");
+        request.gitignore_template("Rust");
+        match forge.create_repository(&request.build().unwrap()) {
+            Err(Error::Unsupported(_)) => {}
+            other => panic!("expected the gitignore template to be rejected, got {:?}", other),
+        }
+        assert!(!dir.path().join("remote").join("project.git").exists());
+    }
+
+    #[test]
+    fn names_stay_inside_the_root() {
+        let dir = TempDir::new("create-gh-repo-test").unwrap();
+        let root = Url::from_file_path(dir.path().join("remote")).unwrap();
+        let forge = LocalForge::new(Some(root.as_str())).unwrap();
+        fs::create_dir_all(dir.path().join("outside.git")).unwrap();
+
+        assert!(forge.repo_path("project").is_ok());
+        assert!(forge.repo_path("owner/project.git").is_ok());
+        for name in &["../outside", "../../x", "/etc/x", "a/b/c", "owner/", "owner/..", "a\\..\\b",
+                      "", "."] {
+            match forge.repo_path(name) {
+                Err(Error::InvalidValue(_)) => {}
+                other => panic!("expected {:?} to be rejected, got {:?}", name, other),
+            }
+        }
+
+        assert!(forge.delete_repository("../outside").is_err());
+        assert!(dir.path().join("outside.git").exists());
+    }
+}
@@ -1,31 +1,36 @@
-#[macro_use]
-extern crate serde_derive;
-extern crate serde;
+// serde_derive 0.9 wraps its generated impls in a named const, which newer
+// compilers report for every derive
+#![allow(non_local_definitions)]
+
+extern crate create_gh_repo;
 extern crate serde_json;
 extern crate tempfile;
-extern crate reqwest;
-extern crate git2;
-extern crate url;
 #[macro_use]
 extern crate clap;
 #[macro_use]
-extern crate nom;
-#[macro_use]
 extern crate log;
 extern crate env_logger;
 extern crate notify;
+extern crate sodiumoxide;
+extern crate url;
+#[macro_use]
+extern crate serde_derive;
 
 mod cli;
-mod git;
-mod error;
+mod batch;
 
-use git::GitMode;
-use error::{Error, Result};
+use cli::{Command, CommandOptions};
+use create_gh_repo::git::{self, GitMode};
+use create_gh_repo::credentials::{self, CredentialStore};
+use create_gh_repo::error::{Error, Result};
+use create_gh_repo::forge::{self, Forge, ForgeKind};
+use create_gh_repo::github::{self, GitHubClient};
+use create_gh_repo::manifest::{CreateRequest, JsonTemplate};
+use create_gh_repo::oauth::DeviceFlow;
+use create_gh_repo::secret::{self, Secret};
+use create_gh_repo::summary::Summary;
+use create_gh_repo::templates::{self, LocalFiles};
 
-use reqwest::Client;
-use reqwest::header::{UserAgent, Authorization, Basic};
-use serde::Serialize;
-use serde_json as json;
 use tempfile::NamedTempFile;
 use notify::{Watcher, RecommendedWatcher};
 
@@ -33,214 +38,404 @@ use std::thread;
 use std::sync::mpsc::{channel, TryRecvError};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::process::Command;
-use std::io::{Write, Read};
+use std::process;
+use std::io::{self, Write, Read};
 use std::fs::{remove_file, File};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Serialize, Deserialize, Debug)]
-struct CreateRequest {
-    name: String,
-    description: String,
-    homepage: String,
-    private: bool,
-    has_issues: bool,
-    has_wiki: bool,
-    has_downloads: bool,
-    auto_init: bool,
-    gitignore_template: String,
-    license_template: String,
-}
+fn main() {
+    env_logger::init().map_err(error).unwrap();
+    sodiumoxide::init().expect("could not initialise libsodium");
 
-impl Default for CreateRequest {
-    fn default() -> CreateRequest {
-        CreateRequest {
-            name: "".into(),
-            description: "".into(),
-            homepage: "".into(),
-            private: false,
-            has_issues: true,
-            has_wiki: false,
-            has_downloads: false,
-            auto_init: true,
-            gitignore_template: "".into(),
-            license_template: "".into(),
+    let mut options = cli::get_options(None).map_err(error).unwrap();
+    match options.command.clone() {
+        Command::Login { client_id, scopes } => return login(&options, client_id, &scopes),
+        Command::Auth { action, host, account } => {
+            return auth(&action, &host, account.as_deref())
+        }
+        _ => {}
+    }
+
+    if let Some(app) = options.app.clone() {
+        let api_root = github::api_root(options.forge_url.as_deref());
+        let token = app.installation_token(&api_root, &options.http).map_err(error).unwrap();
+        options.username = Some("x-access-token".into());
+        options.token = Some(token.clone());
+        options.auth = token;
+    }
+
+    let forge_url = options.forge_url.as_deref();
+    let forge = forge::connect(options.forge, forge_url, &options.auth, &options.http)
+        .map_err(error)
+        .unwrap();
+    if let Some(otp) = options.otp.as_ref() {
+        forge.set_otp(otp.clone()).map_err(error).unwrap();
+    }
+
+    match options.command.clone() {
+        Command::Create | Command::Import { .. } => create(&options, &*forge),
+        Command::Fork { repository, organization, name } => {
+            fork(&options,
+                 &*forge,
+                 &repository,
+                 organization.as_deref(),
+                 name.as_deref())
+        }
+        Command::Batch { file, jobs } => {
+            let user = git_user(&options);
+            let pass = options.password.clone().or(options.token.clone());
+            let success = batch::run(forge.clone(), &file, jobs, user, pass)
+                .map_err(error)
+                .unwrap();
+            if !success {
+                std::process::exit(1);
+            }
         }
+        Command::Whoami => whoami(&*forge),
+        Command::Login { .. } | Command::Auth { .. } => {}
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct CreateResponse {
-    clone_url: String,
+fn whoami(forge: &dyn Forge) {
+    let info = forge.token_info().map_err(error).unwrap();
+    println!("Logged in as: {}", info.login);
+    match info.scopes {
+        Some(ref scopes) if scopes.is_empty() => println!("Scopes: none"),
+        Some(ref scopes) => println!("Scopes: {}", scopes.join(", ")),
+        None => println!("Scopes: not reported for this kind of token"),
+    }
+    println!("Expires: {}", info.expires.as_ref().map_or("never", |x| &**x));
+    if let Some(limit) = info.rate_limit {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        println!("Rate limit: {} of {} remaining, resets in {} minutes",
+                 limit.remaining,
+                 limit.limit,
+                 limit.reset.saturating_sub(now).div_ceil(60));
+    }
 }
 
-trait JsonTemplate
-    where Self: Sized
-{
-    fn to_template(&self) -> String;
-    fn from_template(&str) -> Result<Self>;
+fn login(options: &CommandOptions, client_id: Option<String>, scopes: &str) {
+    let client_id = client_id.or(option_env!("CREATE_GH_REPO_CLIENT_ID").map(|x| x.to_string()))
+        .ok_or(Error::MissingParameter("client-id".into()))
+        .map_err(error)
+        .unwrap();
+    let flow = DeviceFlow::new(client_id, &options.http).map_err(error).unwrap();
+    let code = flow.device_code(scopes).map_err(error).unwrap();
+    println!("Open {} and enter the code: {}", code.verification_uri, code.user_code);
+
+    let token = flow.wait_for_token(&code).map_err(error).unwrap();
+    let user = GitHubClient::with_options(&*token, &options.http)
+        .and_then(|client| client.current_user())
+        .map_err(error)
+        .unwrap();
+
+    let passphrase = credentials::passphrase().map_err(error).unwrap();
+    let mut store = CredentialStore::open(passphrase).map_err(error).unwrap();
+    store.add("github.com", &user.login, token);
+    store.switch("github.com", &user.login).map_err(error).unwrap();
+    store.save().map_err(error).unwrap();
+    println!("Logged in as {}, the token will be used for future runs", user.login);
 }
 
-impl JsonTemplate for CreateRequest {
-    fn to_template(&self) -> String {
-        let mut buf = String::new();
+fn auth(action: &str, host: &str, account: Option<&str>) {
+    let passphrase = credentials::passphrase().map_err(error).unwrap();
+    let mut store = CredentialStore::open(passphrase).map_err(error).unwrap();
+    let account = account.unwrap_or("");
 
-        fn wrap<T>(item: &T) -> String
-            where T: Serialize
-        {
-            json::to_string_pretty(item).unwrap()
+    match action {
+        "add" => {
+            let token = credentials::read_secret("Token").map_err(error).unwrap();
+            store.add(host, account, token);
+        }
+        "remove" => store.remove(host, account).map_err(error).unwrap(),
+        "switch" => store.switch(host, account).map_err(error).unwrap(),
+        _ => {
+            for (host, account, active) in store.accounts() {
+                println!("{} {} {}", if active { "*" } else { " " }, host, account);
+            }
+            return;
         }
-
-        buf.push_str(&*format!(r#"{{
-    //Required. The name of the repository
-    "name": {name},
-    //A short description of the repository
-    "description": {description},
-    //A URL with more information about the repository
-    "homepage": {homepage},
-    //Set to true to create a private repository
-    "private": {private},
-    //Set to true to enable issues for the repository
-    "has_issues": {has_issues},
-    //Set to true to enable the wiki for the repository
-    "has_wiki": {has_wiki},
-    //Set to true to enable downloads for the repository
-    "has_downloads": {has_downloads},
-    //Pass true to create an initial commit with empty README
-    "auto_init": {auto_init},
-    //Desired language or platform .gitignore template to apply. For example, "Haskell"
-    "gitignore_template": {gitignore_template},
-    //Desired LICENSE template to apply. For example, "mit" or "mozilla"
-    "license_template": {license_template}
-}}"#,
-                               name = wrap(&self.name),
-                               description = wrap(&self.description),
-                               homepage = wrap(&self.homepage),
-                               private = wrap(&self.private),
-                               has_issues = wrap(&self.has_issues),
-                               has_wiki = wrap(&self.has_wiki),
-                               has_downloads = wrap(&self.has_downloads),
-                               auto_init = wrap(&self.auto_init),
-                               gitignore_template = wrap(&self.gitignore_template),
-                               license_template = wrap(&self.license_template)));
-
-        buf
     }
+    store.save().map_err(error).unwrap();
+}
 
-    fn from_template(str: &str) -> Result<Self> {
-        use nom::rest_s;
-        named!(strip_comments<&str, String>, fold_many0!(alt!(chain!(
-                val: take_until_s!(" //") ~
-                is_not_s!("\r\n")~
-                tag_s!("\r")?~
-                tag_s!("\n"),
-                || val) |
-                rest_s),
-            String::new(), |mut acc: String, item: &str| {
-                acc.push_str(item);
-                acc
-        }));
-        let result = strip_comments(str);
-
-        if !result.is_done() {
-            return Err(Error::Nom);
-        }
+fn fork(options: &CommandOptions,
+        forge: &dyn Forge,
+        repository: &str,
+        organization: Option<&str>,
+        name: Option<&str>) {
+    let dir = options.directory.as_deref();
+    let res = forge::fork(forge, repository, organization, name).map_err(error).unwrap();
+    println!("Repository Forked: {}", res.repository.clone_url);
+
+    let repo_dir = git::clone(&res.repository.clone_url, dir).map_err(error).unwrap();
+    println!("Cloned into: {}", repo_dir);
 
-        json::from_str(&*result.unwrap().1).map_err(|e| e.into())
+    if let Some(parent) = res.parent {
+        let repo_dir = git::add_remote("upstream", &parent.clone_url, Some(&*repo_dir))
+            .map_err(error)
+            .unwrap();
+        println!("Added upstream remote for: {}", repo_dir);
     }
 }
 
+fn git_user(options: &CommandOptions) -> Option<String> {
+    match options.username {
+        None if options.forge == ForgeKind::GitLab => Some("oauth2".into()),
+        // Any user name is accepted with a token, and batch workers must not prompt for one
+        None if options.token.is_some() => Some("x-access-token".into()),
+        ref username => username.clone(),
+    }
+}
 
-fn main() {
-    env_logger::init().map_err(error).unwrap();
+fn create(options: &CommandOptions, forge: &dyn Forge) {
+    let dir = options.directory.as_deref();
+    let user = git_user(options);
+    let user = user.as_deref();
+    let pass = options.password.as_ref();
+    let token = options.token.as_ref();
 
-    let options = cli::get_options(None).map_err(error).unwrap();
-    let dir = options.directory.as_ref().map(|x| &**x);
-    let user = options.username.as_ref().map(|x| &**x);
-    let pass = options.password.as_ref().map(|x| &**x);
-    let token = options.token.as_ref().map(|x| &**x);
+    let available_templates = if options.list_templates {
+        forge.list_templates().map_err(error).unwrap()
+    } else {
+        Vec::new()
+    };
+    let import_source = match options.command {
+        Command::Import { ref source } => Some(&**source),
+        _ => None,
+    };
 
+    let name = match import_source {
+        Some(source) => git::get_url_name(source),
+        None if options.mode == GitMode::Init => git::get_dir_name(dir).ok(),
+        None => git::get_repo_name(dir).ok(),
+    };
     let default_params = CreateRequest {
-        name: git::get_repo_name(dir).unwrap_or("".into()),
-        auto_init: options.mode != GitMode::Push,
+        name: name.unwrap_or("".into()),
+        auto_init: options.mode != GitMode::Push && options.mode != GitMode::Init &&
+                   import_source.is_none(),
+        template: options.template.clone().unwrap_or_default(),
+        available_templates,
         ..Default::default()
     };
     let request_params =
-        prompt_create_params(&options.editor, &default_params).map_err(error).unwrap();
+        prompt_create_params(&options.editor, &default_params, options.forge)
+            .map_err(error)
+            .unwrap();
 
     if request_params.is_none() {
         println!("Request parameters not saved, repository not created.");
         return;
     }
 
-    let request_params = request_params.unwrap();
-
-    // TODO: Need to handle errors from Github api
-    let api_url = "https://api.github.com/user/repos";
-    let client = Client::new().unwrap();
-    let res: CreateResponse = client.post(api_url)
-        .header(Authorization(Basic{
-            username: options.auth,
-            password: None
-        }))
-        .header(UserAgent(format!("create-gh-repo/{}", crate_version!())))
-        .json(&request_params)
-        .send()
-        .map_err(error)
-        .unwrap()
-        .json()
-        .map_err(error)
-        .unwrap();
+    let mut request_params = request_params.unwrap();
+    if import_source.is_some() {
+        request_params.auto_init = false;
+        request_params.clear_gitignore();
+        request_params.clear_license();
+    }
+    let init = options.mode == GitMode::Init && import_source.is_none();
+    let local_files = match import_source {
+        Some(_) => LocalFiles::Nothing,
+        None => LocalFiles::select(options.mode, options.local_templates, &request_params),
+    };
+    if local_files == LocalFiles::All {
+        request_params.auto_init = false;
+    }
 
+    let resolved = request_params.resolve().map_err(error).unwrap();
+    let (mut files, mut replace) = (Vec::new(), Vec::new());
+    if local_files != LocalFiles::Nothing {
+        let author = git::get_config_value("user.name").unwrap_or_default();
+        let rendered = templates::render(forge, &request_params, &author, local_files)
+            .map_err(error)
+            .unwrap();
+        let workdir = git::local_dir(dir).map_err(error).unwrap();
+        let gitignore_name = request_params.gitignore_names().join(",");
+        files = local_template_files(&workdir, rendered, &gitignore_name, &mut replace)
+            .map_err(error)
+            .unwrap();
+        request_params.clear_gitignore();
+        if local_files == LocalFiles::All {
+            request_params.clear_license();
+        }
+    }
+    if init {
+        git::check_init(dir).map_err(error).unwrap();
+    }
+    request_params.rollback = options.rollback;
+    // Only touch the local repository once the remote exists, so a failed
+    // create can simply be run again
+    let res = forge::create(forge, &request_params).map_err(error).unwrap();
     println!("Repository Created: {}", res.clone_url);
-    match options.mode {
+
+    if init {
+        let repo_dir = or_rollback(options,
+                                   forge,
+                                   &res.full_name,
+                                   git::init(dir, &options.initial_branch, &files, &replace));
+        println!("Initialized repository: {}", repo_dir);
+    } else if !files.is_empty() {
+        let names: Vec<&str> = files.iter().map(|(name, _)| &**name).collect();
+        let message = format!("Add {}", names.join(", "));
+        or_rollback(options,
+                    forge,
+                    &res.full_name,
+                    git::commit_files(dir, &files, &replace, &message));
+        println!("Committed: {}", names.join(", "));
+    }
+
+    let mut summary = Summary::new();
+    let key = options.deploy_key.as_ref().map(|k| (&**k, options.deploy_key_write));
+    forge.apply_settings(&request_params, &resolved, &res.full_name, key, &mut summary);
+
+    if let Some(source) = import_source {
+        let result = or_rollback(options,
+                                 forge,
+                                 &res.full_name,
+                                 git::mirror(source, &res.clone_url, user, pass.or(token)));
+        println!("Imported {} refs from: {}", result.refs, source);
+        for (name, status) in &result.rejected {
+            println!("Rejected {}: {}", name, status);
+        }
+    } else {
+        or_rollback(options,
+                    forge,
+                    &res.full_name,
+                    git_mode(options.mode, &res.clone_url, dir, user, pass.or(token)));
+    }
+
+    summary.print();
+    if !summary.is_success() {
+        if options.rollback {
+            delete_created(forge, &res.full_name);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn or_rollback<T>(options: &CommandOptions, forge: &dyn Forge, repo: &str, result: Result<T>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            if options.rollback {
+                delete_created(forge, repo);
+            }
+            error(err)
+        }
+    }
+}
+
+fn delete_created(forge: &dyn Forge, repo: &str) {
+    match forge.delete_repository(repo) {
+        Ok(()) => println!("Rolled back, deleted repository: {}", repo),
+        Err(err) => {
+            println!("Could not delete repository {}: {}",
+                     repo,
+                     secret::scrub(&err.to_string()))
+        }
+    }
+}
+
+fn local_template_files(workdir: &Path,
+                        files: Vec<(String, String)>,
+                        gitignore_template: &str,
+                        replace: &mut Vec<String>)
+                        -> Result<Vec<(String, String)>> {
+    let mut prepared = Vec::new();
+    for (name, contents) in files {
+        let path = workdir.join(&name);
+        if !path.exists() {
+            prepared.push((name, contents));
+            continue;
+        }
+        if name != ".gitignore" {
+            continue;
+        }
+
+        let mut existing = String::new();
+        File::open(&path)?.read_to_string(&mut existing)?;
+        let merged = templates::merge_gitignore(&existing, &contents, gitignore_template);
+        if let Some(merged) = merged {
+            print!("{}", templates::diff(&name, &existing, &merged));
+            if confirm("Add the template to .gitignore?")? {
+                replace.push(name.clone());
+                prepared.push((name, merged));
+            }
+        }
+    }
+    Ok(prepared)
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+fn git_mode(mode: GitMode,
+            clone_url: &str,
+            dir: Option<&str>,
+            user: Option<&str>,
+            pass: Option<&Secret>)
+            -> Result<()> {
+    match mode {
         GitMode::Create => {}
         GitMode::Clone => {
-            let repo_dir = git::clone(&res.clone_url, dir).map_err(error).unwrap();
+            let repo_dir = git::clone(clone_url, dir)?;
             println!("Cloned into: {}", repo_dir);
         }
         GitMode::Remote => {
-            let repo_dir = git::remotes(&res.clone_url, dir).map_err(error).unwrap();
+            let repo_dir = git::remotes(clone_url, dir)?;
             println!("Updated remotes for: {}", repo_dir);
         }
-        GitMode::Push => {
-            let repo_dir = git::remotes(&res.clone_url, dir).map_err(error).unwrap();
+        GitMode::Push | GitMode::Init => {
+            let repo_dir = git::remotes(clone_url, dir)?;
             println!("Updated remotes for: {}", repo_dir);
-            let repo_dir = git::push(dir, user, pass.or(token)).map_err(error).unwrap();
+            let repo_dir = git::push(dir, user, pass)?;
             println!("Pushed repository: {}", repo_dir);
         }
         GitMode::Rebase => {
             unimplemented!();
         }
     }
+    Ok(())
 }
 
 fn error<E>(err: E) -> !
     where E: std::error::Error
 {
-    error!("Error: {:?}", err);
-    println!("Error: {}", err.description());
+    error!("Error: {}", secret::scrub(&format!("{:?}", err)));
+    println!("Error: {}", secret::scrub(&err.to_string()));
     std::process::exit(1)
 }
 
-fn prompt_create_params(editor: &str, options: &CreateRequest) -> Result<Option<CreateRequest>> {
-    let mut tmp_file = try!(NamedTempFile::new());
-    let _ = write!(tmp_file, "{}", options.to_template());
+fn prompt_create_params(editor: &str,
+                        options: &CreateRequest,
+                        forge: ForgeKind)
+                        -> Result<Option<CreateRequest>> {
+    let mut tmp_file = NamedTempFile::new()?;
+    let _ = write!(tmp_file, "{}", options.to_forge_template(forge));
     let _ = tmp_file.sync_all();
-    let path = try!(tmp_file.path().to_str().ok_or(Error::InvalidTargetDir)).to_string();
+    let path = tmp_file.path().to_str().ok_or(Error::InvalidTargetDir)?.to_string();
     {
-        let _ = try!(tmp_file.persist(&path));
+        let _ = tmp_file.persist(&path)?;
     }
     let (tx, rx) = channel();
     let closed = Arc::new(AtomicBool::new(false));
     let written = Arc::new(AtomicBool::new(false));
 
-    let mut watcher: RecommendedWatcher = try!(Watcher::new(tx));
+    let mut watcher: RecommendedWatcher = Watcher::new(tx)?;
     {
         let closed = closed.clone();
         let written = written.clone();
         let path = path.clone();
         thread::spawn(move || -> Result<()> {
-            try!(watcher.watch(path));
+            watcher.watch(path)?;
             loop {
                 if closed.load(Ordering::Relaxed) {
                     return Ok(());
@@ -257,7 +452,7 @@ fn prompt_create_params(editor: &str, options: &CreateRequest) -> Result<Option<
         });
     }
 
-    let status = try!(Command::new(editor).arg(&path).status());
+    let status = process::Command::new(editor).arg(&path).status()?;
     {
         closed.store(true, Ordering::Relaxed);
     }
@@ -265,9 +460,9 @@ fn prompt_create_params(editor: &str, options: &CreateRequest) -> Result<Option<
     if !status.success() || !written.load(Ordering::Relaxed) {
         return Ok(None);
     }
-    let mut tmp_file = try!(File::open(&path));
+    let mut tmp_file = File::open(&path)?;
     let mut text = String::new();
     let _ = tmp_file.read_to_string(&mut text);
-    try!(remove_file(&path));
-    Ok(Some(try!(CreateRequest::from_template(&*text))))
+    remove_file(&path)?;
+    Ok(Some(CreateRequest::from_template(&text)?))
 }
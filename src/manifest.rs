@@ -0,0 +1,518 @@
+use error::{Error, Result};
+use forge::ForgeKind;
+use labels;
+use secret::Secret;
+
+use serde::Serialize;
+use serde_json as json;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub homepage: String,
+    #[serde(default)]
+    pub private: bool,
+    #[serde(default = "default_true")]
+    pub has_issues: bool,
+    #[serde(default)]
+    pub has_wiki: bool,
+    #[serde(default)]
+    pub has_downloads: bool,
+    #[serde(default = "default_true")]
+    pub auto_init: bool,
+    #[serde(default)]
+    pub gitignore_template: String,
+    #[serde(default)]
+    pub license_template: String,
+    #[serde(default, skip_serializing)]
+    pub organization: String,
+    #[serde(default, skip_serializing)]
+    pub collaborators: Vec<Collaborator>,
+    #[serde(default, skip_serializing)]
+    pub teams: Vec<Team>,
+    #[serde(default, skip_serializing)]
+    pub labels: Option<json::Value>,
+    #[serde(default, skip_serializing)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default, skip_serializing)]
+    pub actions_secrets: Vec<ActionsValue>,
+    #[serde(default, skip_serializing)]
+    pub actions_variables: Vec<ActionsValue>,
+    #[serde(default, skip_serializing)]
+    pub template: String,
+    #[serde(default, skip_serializing)]
+    pub include_all_branches: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub available_templates: Vec<String>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub rollback: bool,
+    #[serde(default, skip_serializing)]
+    pub default_branch: String,
+    #[serde(default, skip_serializing)]
+    pub trust_model: String,
+    #[serde(default, skip_serializing)]
+    pub gitignores: String,
+    #[serde(default, skip_serializing)]
+    pub license: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Collaborator {
+    pub user: String,
+    pub permission: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Team {
+    pub slug: String,
+    pub permission: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default = "default_content_type")]
+    pub content_type: String,
+    #[serde(default = "default_events")]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub secret_env: String,
+}
+
+fn default_content_type() -> String {
+    "json".into()
+}
+
+fn default_events() -> Vec<String> {
+    vec!["push".into()]
+}
+
+impl Webhook {
+    pub fn secret(&self) -> Result<Option<Secret>> {
+        if self.secret_env.is_empty() {
+            return Ok(None);
+        }
+        env::var(&self.secret_env)
+            .map(|secret| Some(Secret::new(secret)))
+            .map_err(|_| Error::MissingParameter(self.secret_env.clone()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionsValue {
+    pub name: String,
+    #[serde(default)]
+    pub env: String,
+    #[serde(default)]
+    pub file: String,
+}
+
+impl ActionsValue {
+    pub fn value(&self) -> Result<String> {
+        if !self.env.is_empty() {
+            env::var(&self.env).map_err(|_| Error::MissingParameter(self.env.clone()))
+        } else if !self.file.is_empty() {
+            let mut text = String::new();
+            File::open(&self.file)?.read_to_string(&mut text)?;
+            Ok(text)
+        } else {
+            Err(Error::InvalidManifest(format!("{} requires \"env\" or \"file\"", self.name)))
+        }
+    }
+
+    pub fn secret(&self) -> Result<Secret> {
+        self.value().map(Secret::new)
+    }
+}
+
+pub struct ResolvedParams {
+    pub labels: Option<Vec<labels::Label>>,
+    pub hook_secrets: Vec<Option<Secret>>,
+    pub actions_secrets: Vec<Secret>,
+    pub actions_variables: Vec<String>,
+}
+
+impl CreateRequest {
+    // Gitea names its template fields differently and takes several .gitignores
+    pub fn gitignore_names(&self) -> Vec<&str> {
+        self.gitignore_template
+            .split(',')
+            .chain(self.gitignores.split(','))
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+
+    pub fn license_key(&self) -> &str {
+        if self.license_template.is_empty() {
+            &self.license
+        } else {
+            &self.license_template
+        }
+    }
+
+    pub fn clear_gitignore(&mut self) {
+        self.gitignore_template.clear();
+        self.gitignores.clear();
+    }
+
+    pub fn clear_license(&mut self) {
+        self.license_template.clear();
+        self.license.clear();
+    }
+
+    pub fn has_github_settings(&self) -> bool {
+        !self.collaborators.is_empty() || !self.teams.is_empty() || self.labels.is_some() ||
+        !self.webhooks.is_empty() || !self.actions_secrets.is_empty() ||
+        !self.actions_variables.is_empty()
+    }
+
+    pub fn resolve(&self) -> Result<ResolvedParams> {
+        let labels = match self.labels {
+            Some(ref labels) => Some(labels::load(labels)?),
+            None => None,
+        };
+        let hook_secrets = self.webhooks.iter().map(|h| h.secret()).collect::<Result<_>>()?;
+        let actions_secrets =
+            self.actions_secrets.iter().map(|v| v.secret()).collect::<Result<_>>()?;
+        let actions_variables =
+            self.actions_variables.iter().map(|v| v.value()).collect::<Result<_>>()?;
+
+        Ok(ResolvedParams {
+            labels,
+            hook_secrets,
+            actions_secrets,
+            actions_variables,
+        })
+    }
+}
+
+impl Default for CreateRequest {
+    fn default() -> CreateRequest {
+        CreateRequest {
+            name: "".into(),
+            description: "".into(),
+            homepage: "".into(),
+            private: false,
+            has_issues: true,
+            has_wiki: false,
+            has_downloads: false,
+            auto_init: true,
+            gitignore_template: "".into(),
+            license_template: "".into(),
+            organization: "".into(),
+            collaborators: Vec::new(),
+            teams: Vec::new(),
+            labels: None,
+            webhooks: Vec::new(),
+            actions_secrets: Vec::new(),
+            actions_variables: Vec::new(),
+            template: "".into(),
+            include_all_branches: false,
+            available_templates: Vec::new(),
+            rollback: false,
+            default_branch: "".into(),
+            trust_model: "".into(),
+            gitignores: "".into(),
+            license: "".into(),
+        }
+    }
+}
+
+pub struct CreateRequestBuilder {
+    request: CreateRequest,
+}
+
+impl CreateRequestBuilder {
+    pub fn new<S>(name: S) -> CreateRequestBuilder
+        where S: Into<String>
+    {
+        CreateRequestBuilder {
+            request: CreateRequest { name: name.into(), ..Default::default() },
+        }
+    }
+
+    pub fn description<S>(&mut self, description: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.request.description = description.into();
+        self
+    }
+
+    pub fn homepage<S>(&mut self, homepage: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.request.homepage = homepage.into();
+        self
+    }
+
+    pub fn private(&mut self, private: bool) -> &mut Self {
+        self.request.private = private;
+        self
+    }
+
+    pub fn has_issues(&mut self, has_issues: bool) -> &mut Self {
+        self.request.has_issues = has_issues;
+        self
+    }
+
+    pub fn has_wiki(&mut self, has_wiki: bool) -> &mut Self {
+        self.request.has_wiki = has_wiki;
+        self
+    }
+
+    pub fn has_downloads(&mut self, has_downloads: bool) -> &mut Self {
+        self.request.has_downloads = has_downloads;
+        self
+    }
+
+    pub fn auto_init(&mut self, auto_init: bool) -> &mut Self {
+        self.request.auto_init = auto_init;
+        self
+    }
+
+    pub fn gitignore_template<S>(&mut self, template: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.request.gitignore_template = template.into();
+        self
+    }
+
+    pub fn license_template<S>(&mut self, template: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.request.license_template = template.into();
+        self
+    }
+
+    pub fn organization<S>(&mut self, organization: S) -> &mut Self
+        where S: Into<String>
+    {
+        self.request.organization = organization.into();
+        self
+    }
+
+    pub fn template<S>(&mut self, template: S, include_all_branches: bool) -> &mut Self
+        where S: Into<String>
+    {
+        self.request.template = template.into();
+        self.request.include_all_branches = include_all_branches;
+        self
+    }
+
+    pub fn collaborator<S, P>(&mut self, user: S, permission: P) -> &mut Self
+        where S: Into<String>,
+              P: Into<String>
+    {
+        self.request.collaborators.push(Collaborator {
+            user: user.into(),
+            permission: permission.into(),
+        });
+        self
+    }
+
+    pub fn team<S, P>(&mut self, slug: S, permission: P) -> &mut Self
+        where S: Into<String>,
+              P: Into<String>
+    {
+        self.request.teams.push(Team {
+            slug: slug.into(),
+            permission: permission.into(),
+        });
+        self
+    }
+
+    pub fn webhook(&mut self, webhook: Webhook) -> &mut Self {
+        self.request.webhooks.push(webhook);
+        self
+    }
+
+    pub fn build(self) -> Result<CreateRequest> {
+        if self.request.name.is_empty() {
+            return Err(Error::MissingParameter("name".into()));
+        }
+        Ok(self.request)
+    }
+}
+
+pub trait JsonTemplate
+    where Self: Sized
+{
+    fn to_template(&self) -> String;
+    fn from_template(_: &str) -> Result<Self>;
+}
+
+impl JsonTemplate for CreateRequest {
+    fn to_template(&self) -> String {
+        let mut buf = String::new();
+
+        fn wrap<T>(item: &T) -> String
+            where T: Serialize
+        {
+            json::to_string_pretty(item).unwrap()
+        }
+
+        let mut available_templates = String::new();
+        if !self.available_templates.is_empty() {
+            available_templates.push_str("    //Available templates:\n");
+            for template in &self.available_templates {
+                available_templates.push_str(&format!("    //    {}\n", template));
+            }
+        }
+
+        buf.push_str(&format!(r#"{{
+    //Required. The name of the repository
+    "name": {name},
+    //A short description of the repository
+    "description": {description},
+    //A URL with more information about the repository
+    "homepage": {homepage},
+    //Set to true to create a private repository
+    "private": {private},
+    //Set to true to enable issues for the repository
+    "has_issues": {has_issues},
+    //Set to true to enable the wiki for the repository
+    "has_wiki": {has_wiki},
+    //Set to true to enable downloads for the repository
+    "has_downloads": {has_downloads},
+    //Pass true to create an initial commit with empty README
+    "auto_init": {auto_init},
+    //Desired language or platform .gitignore template to apply. For example, "Haskell"
+    "gitignore_template": {gitignore_template},
+    //Desired LICENSE template to apply. For example, "mit" or "mozilla"
+    "license_template": {license_template},
+    //Create the repository under this organization instead of your user account
+    "organization": {organization},
+    //Users to grant access to. For example, [{{"user": "octocat", "permission": "push"}}]
+    "collaborators": {collaborators},
+    //Organization teams to grant access to. For example, [{{"slug": "devs", "permission": "pull"}}]
+    "teams": {teams},
+    //Labels to replace GitHub's defaults with, inline or as a path to a JSON or TOML file.
+    //For example, [{{"name": "bug", "color": "d73a4a", "from": "defect"}}]. null keeps the defaults
+    "labels": {labels},
+    //Webhooks to add. The secret is read from the environment variable named by "secret_env".
+    //For example, [{{"url": "https://ci.example.com/hook", "content_type": "json",
+    //"events": ["push", "pull_request"], "secret_env": "CI_HOOK_SECRET"}}]
+    "webhooks": {webhooks},
+    //GitHub Actions secrets, read from an environment variable or a file.
+    //For example, [{{"name": "NPM_TOKEN", "env": "NPM_TOKEN"}}, {{"name": "KEY", "file": "key.pem"}}]
+    "actions_secrets": {actions_secrets},
+    //GitHub Actions variables, read the same way as "actions_secrets"
+    "actions_variables": {actions_variables},
+    //Create the repository from this template repository. For example, "octocat/skeleton"
+{available_templates}    "template": {template},
+    //Set to true to copy every branch of the template repository, not just the default branch
+    "include_all_branches": {include_all_branches}
+}}"#,
+                               name = wrap(&self.name),
+                               description = wrap(&self.description),
+                               homepage = wrap(&self.homepage),
+                               private = wrap(&self.private),
+                               has_issues = wrap(&self.has_issues),
+                               has_wiki = wrap(&self.has_wiki),
+                               has_downloads = wrap(&self.has_downloads),
+                               auto_init = wrap(&self.auto_init),
+                               gitignore_template = wrap(&self.gitignore_template),
+                               license_template = wrap(&self.license_template),
+                               organization = wrap(&self.organization),
+                               collaborators = wrap(&self.collaborators),
+                               teams = wrap(&self.teams),
+                               labels = wrap(&self.labels),
+                               webhooks = wrap(&self.webhooks),
+                               actions_secrets = wrap(&self.actions_secrets),
+                               actions_variables = wrap(&self.actions_variables),
+                               available_templates = available_templates,
+                               template = wrap(&self.template),
+                               include_all_branches = wrap(&self.include_all_branches)));
+
+        buf
+    }
+
+    fn from_template(str: &str) -> Result<Self> {
+        let text = strip_comments(str)?;
+        json::from_str(&text).map_err(|e| e.into())
+    }
+}
+
+impl CreateRequest {
+    pub fn to_forge_template(&self, kind: ForgeKind) -> String {
+        match kind {
+            ForgeKind::Gitea => self.to_gitea_template(),
+            _ => self.to_template(),
+        }
+    }
+
+    fn to_gitea_template(&self) -> String {
+        fn wrap<T>(item: &T) -> String
+            where T: Serialize
+        {
+            json::to_string_pretty(item).unwrap()
+        }
+
+        format!(r#"{{
+    //Required. The name of the repository
+    "name": {name},
+    //A short description of the repository
+    "description": {description},
+    //Set to true to create a private repository
+    "private": {private},
+    //Pass true to create an initial commit with README, .gitignore and LICENSE
+    "auto_init": {auto_init},
+    //Comma separated .gitignore templates to apply. For example, "Rust,VisualStudioCode"
+    "gitignores": {gitignores},
+    //LICENSE template to apply. For example, "MIT"
+    "license": {license},
+    //Name of the default branch. Leave empty to use the server's default
+    "default_branch": {default_branch},
+    //Commit signature trust model. One of "default", "collaborator", "committer" or
+    //"collaboratorcommitter"
+    "trust_model": {trust_model},
+    //Create the repository under this organization instead of your user account
+    "organization": {organization}
+}}"#,
+                name = wrap(&self.name),
+                description = wrap(&self.description),
+                private = wrap(&self.private),
+                auto_init = wrap(&self.auto_init),
+                gitignores = wrap(&self.gitignores),
+                license = wrap(&self.license),
+                default_branch = wrap(&self.default_branch),
+                trust_model = wrap(&self.trust_model),
+                organization = wrap(&self.organization))
+    }
+}
+
+// The nom 1.2 macros expand to slicing that clippy reads as manual prefix stripping
+#[allow(clippy::manual_strip)]
+pub fn strip_comments(str: &str) -> Result<String> {
+    use nom::rest_s;
+    named!(strip_comments<&str, String>, fold_many0!(alt!(chain!(
+            val: take_until_s!(" //") ~
+            is_not_s!("\r\n")~
+            tag_s!("\r")?~
+            tag_s!("\n"),
+            || val) |
+            rest_s),
+        String::new(), |mut acc: String, item: &str| {
+            acc.push_str(item);
+            acc
+    }));
+    let result = strip_comments(str);
+
+    if !result.is_done() {
+        return Err(Error::Nom);
+    }
+
+    Ok(result.unwrap().1)
+}
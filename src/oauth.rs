@@ -0,0 +1,100 @@
+use error::{Error, Result};
+use github::USER_AGENT;
+use http::{Client, HttpOptions, Method};
+
+use hyper::header::{Accept, UserAgent};
+
+use std::thread;
+use std::time::Duration;
+
+const OAUTH_ROOT: &str = "https://github.com";
+const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+#[derive(Serialize, Debug)]
+struct DeviceCodeRequest<'a> {
+    client_id: &'a str,
+    scope: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct TokenRequest<'a> {
+    client_id: &'a str,
+    device_code: &'a str,
+    grant_type: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+}
+
+pub struct DeviceFlow {
+    client: Client,
+    client_id: String,
+}
+
+impl DeviceFlow {
+    pub fn new<S>(client_id: S, options: &HttpOptions) -> Result<DeviceFlow>
+        where S: Into<String>
+    {
+        Ok(DeviceFlow {
+            client: Client::new(OAUTH_ROOT, options)?,
+            client_id: client_id.into(),
+        })
+    }
+
+    pub fn device_code(&self, scope: &str) -> Result<DeviceCode> {
+        let body = DeviceCodeRequest {
+            client_id: &self.client_id,
+            scope,
+        };
+        let request = self.client
+            .request(Method::Post, format!("{}/login/device/code", OAUTH_ROOT))
+            .header(Accept::json())
+            .header(UserAgent(USER_AGENT.to_string()))
+            .json(&body)?;
+        let mut res = self.client.send(&request)?;
+        res.json()
+    }
+
+    pub fn wait_for_token(&self, code: &DeviceCode) -> Result<String> {
+        let body = TokenRequest {
+            client_id: &self.client_id,
+            device_code: &code.device_code,
+            grant_type: GRANT_TYPE,
+        };
+        let request = self.client
+            .request(Method::Post, format!("{}/login/oauth/access_token", OAUTH_ROOT))
+            .header(Accept::json())
+            .header(UserAgent(USER_AGENT.to_string()))
+            .json(&body)?;
+
+        let mut interval = code.interval.max(1);
+        let mut waited = 0;
+        while waited < code.expires_in {
+            thread::sleep(Duration::from_secs(interval));
+            waited += interval;
+
+            let token: TokenResponse = self.client.send(&request)?.json()?;
+            match (token.access_token, token.error) {
+                (Some(token), _) => return Ok(token),
+                (None, Some(ref e)) if e == "authorization_pending" => {}
+                (None, Some(ref e)) if e == "slow_down" => interval += 5,
+                (None, Some(e)) => return Err(Error::OAuth(e)),
+                (None, None) => return Err(Error::OAuth("empty response".into())),
+            }
+        }
+
+        Err(Error::OAuth("expired_token".into()))
+    }
+}
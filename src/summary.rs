@@ -0,0 +1,61 @@
+use error::Result;
+use secret::scrub;
+
+pub struct Summary {
+    steps: Vec<(String, Option<String>)>,
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Summary {
+    pub fn new() -> Summary {
+        Summary { steps: Vec::new() }
+    }
+
+    pub fn record<S, T>(&mut self, step: S, result: Result<T>) -> Option<T>
+        where S: Into<String>
+    {
+        match result {
+            Ok(value) => {
+                self.steps.push((step.into(), None));
+                Some(value)
+            }
+            Err(err) => {
+                let message = scrub(&err.to_string());
+                error!("Error: {}", message);
+                self.steps.push((step.into(), Some(message)));
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.steps.iter().filter(|&(_, err)| err.is_some()).count()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.steps.iter().all(|(_, err)| err.is_none())
+    }
+
+    pub fn print(&self) {
+        if self.is_empty() {
+            return;
+        }
+
+        println!("Summary:");
+        for (step, err) in &self.steps {
+            match *err {
+                None => println!("    [ok]     {}", step),
+                Some(ref err) => println!("    [failed] {}: {}", step, err),
+            }
+        }
+    }
+}
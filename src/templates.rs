@@ -0,0 +1,327 @@
+use error::Result;
+use forge::Forge;
+use git::GitMode;
+use manifest::CreateRequest;
+
+use std::cmp;
+use std::collections::HashSet;
+
+use time;
+
+const DIFF_CONTEXT: usize = 3;
+
+fn readme(request: &CreateRequest) -> String {
+    if request.description.is_empty() {
+        format!("# {}\n", request.name)
+    } else {
+        format!("# {}\n\n{}\n", request.name, request.description)
+    }
+}
+
+fn fill_license(body: &str, year: i32, author: &str) -> String {
+    body.replace("[year]", &year.to_string())
+        .replace("[yyyy]", &year.to_string())
+        .replace("[fullname]", author)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalFiles {
+    Nothing,
+    Gitignore,
+    All,
+}
+
+impl LocalFiles {
+    // Without auto_init the forge drops the templates, so a repository pushed
+    // from a local directory gets its .gitignore merged locally
+    pub fn select(mode: GitMode, local_templates: bool, request: &CreateRequest) -> LocalFiles {
+        match mode {
+            GitMode::Init => LocalFiles::All,
+            GitMode::Push if local_templates => LocalFiles::All,
+            GitMode::Push if !request.gitignore_names().is_empty() => LocalFiles::Gitignore,
+            _ => LocalFiles::Nothing,
+        }
+    }
+}
+
+pub fn render(forge: &dyn Forge,
+              request: &CreateRequest,
+              author: &str,
+              local: LocalFiles)
+              -> Result<Vec<(String, String)>> {
+    let mut files = Vec::new();
+    if local == LocalFiles::Nothing {
+        return Ok(files);
+    }
+    if local == LocalFiles::All {
+        files.push(("README.md".to_string(), readme(request)));
+    }
+    if local == LocalFiles::All && !request.license_key().is_empty() {
+        let body = forge.license_template(request.license_key())?;
+        let year = time::now().tm_year + 1900;
+        files.push(("LICENSE".to_string(), fill_license(&body, year, author)));
+    }
+    let names = request.gitignore_names();
+    if !names.is_empty() {
+        let mut sources = Vec::new();
+        for name in names {
+            sources.push(forge.gitignore_template(name)?);
+        }
+        files.push((".gitignore".to_string(), sources.join("\n")));
+    }
+    Ok(files)
+}
+
+pub fn merge_gitignore<'a>(existing: &'a str, template: &'a str, name: &str) -> Option<String> {
+    let begin = format!("# BEGIN gitignore template: {}", name);
+    let end = format!("# END gitignore template: {}", name);
+    if existing.lines().any(|line| line.trim() == begin) {
+        return None;
+    }
+
+    let mut seen: HashSet<&str> = existing.lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .collect();
+    let mut section: Vec<&str> = Vec::new();
+    let mut added = false;
+    for line in template.lines().map(|x| x.trim()) {
+        if line.is_empty() {
+            if section.last().is_some_and(|x| !x.is_empty()) {
+                section.push("");
+            }
+        } else if line.starts_with('#') {
+            section.push(line);
+        } else if seen.insert(line) {
+            section.push(line);
+            added = true;
+        }
+    }
+    if !added {
+        return None;
+    }
+    while section.last() == Some(&"") {
+        section.pop();
+    }
+
+    let mut merged = existing.to_string();
+    if !merged.is_empty() {
+        if !merged.ends_with('\n') {
+            merged.push('\n');
+        }
+        merged.push('\n');
+    }
+    merged.push_str(&begin);
+    merged.push('\n');
+    for line in section {
+        merged.push_str(line);
+        merged.push('\n');
+    }
+    merged.push_str(&end);
+    merged.push('\n');
+    Some(merged)
+}
+
+// An empty range is numbered after the line it follows, as in diff -u
+fn hunk_start(start: usize, count: usize) -> usize {
+    if count == 0 { start } else { start + 1 }
+}
+
+pub fn diff(path: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count();
+
+    let start = prefix.saturating_sub(DIFF_CONTEXT);
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+    let context = cmp::min(suffix, DIFF_CONTEXT);
+
+    let old_count = old_end + context - start;
+    let new_count = new_end + context - start;
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    out.push_str(&format!("@@ -{},{} +{},{} @@\n",
+                           hunk_start(start, old_count),
+                           old_count,
+                           hunk_start(start, new_count),
+                           new_count));
+    for line in &old[start..prefix] {
+        out.push_str(&format!(" {}\n", line));
+    }
+    for line in &old[prefix..old_end] {
+        out.push_str(&format!("-{}\n", line));
+    }
+    for line in &new[prefix..new_end] {
+        out.push_str(&format!("+{}\n", line));
+    }
+    for line in &old[old_end..old_end + context] {
+        out.push_str(&format!(" {}\n", line));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::Error;
+    use forge::RemoteRepository;
+    use github::RepoSettings;
+    use manifest::CreateRequest;
+
+    struct Templates;
+
+    impl Forge for Templates {
+        fn create_repository(&self, _request: &CreateRequest) -> Result<RemoteRepository> {
+            Err(Error::Unsupported("create".into()))
+        }
+
+        fn repository(&self, _repo: &str) -> Result<RemoteRepository> {
+            Err(Error::Unsupported("repository".into()))
+        }
+
+        fn delete_repository(&self, _repo: &str) -> Result<()> {
+            Err(Error::Unsupported("delete".into()))
+        }
+
+        fn update_settings(&self, _repo: &str, _settings: &RepoSettings) -> Result<()> {
+            Err(Error::Unsupported("settings".into()))
+        }
+
+        fn gitignore_template(&self, name: &str) -> Result<String> {
+            Ok(format!("# {}\n*.tmp\n", name))
+        }
+
+        fn license_template(&self, key: &str) -> Result<String> {
+            Ok(format!("{} [year] [fullname]\n", key))
+        }
+    }
+
+    fn names(files: &[(String, String)]) -> Vec<&str> {
+        files.iter().map(|(name, _)| &**name).collect()
+    }
+
+    #[test]
+    fn push_mode_merges_the_gitignore() {
+        let mut request = CreateRequest {
+            name: "project".into(),
+            gitignore_template: "Rust".into(),
+            license_template: "mit".into(),
+            ..CreateRequest::default()
+        };
+
+        let local = LocalFiles::select(GitMode::Push, false, &request);
+        assert_eq!(local, LocalFiles::Gitignore);
+        let files = render(&Templates, &request, "Jane Doe", local).unwrap();
+        assert_eq!(files, vec![(".gitignore".to_string(), "# Rust\n*.tmp\n".to_string())]);
+
+        let local = LocalFiles::select(GitMode::Push, true, &request);
+        assert_eq!(local, LocalFiles::All);
+        let files = render(&Templates, &request, "Jane Doe", local).unwrap();
+        assert_eq!(names(&files), vec!["README.md", "LICENSE", ".gitignore"]);
+
+        assert_eq!(LocalFiles::select(GitMode::Clone, false, &request), LocalFiles::Nothing);
+        assert_eq!(LocalFiles::select(GitMode::Init, false, &request), LocalFiles::All);
+
+        request.clear_gitignore();
+        assert_eq!(LocalFiles::select(GitMode::Push, false, &request), LocalFiles::Nothing);
+        request.gitignores = "Rust, Node".into();
+        assert_eq!(LocalFiles::select(GitMode::Push, false, &request), LocalFiles::Gitignore);
+        assert_eq!(request.gitignore_names(), vec!["Rust", "Node"]);
+        request.clear_gitignore();
+        assert!(render(&Templates, &request, "", LocalFiles::Nothing).unwrap().is_empty());
+    }
+
+    #[test]
+    fn readme_title_and_description() {
+        let mut request = CreateRequest { name: "project".into(), ..CreateRequest::default() };
+        assert_eq!(readme(&request), "# project\n");
+
+        request.description = "A test project".into();
+        assert_eq!(readme(&request), "# project\n\nA test project\n");
+    }
+
+    #[test]
+    fn license_placeholders() {
+        let body = "Copyright (c) [year] [fullname]\nCopyright [yyyy] [fullname]\n[name]\n";
+        assert_eq!(fill_license(body, 2024, "Jane Doe"),
+                   "Copyright (c) 2024 Jane Doe\nCopyright 2024 Jane Doe\n[name]\n");
+        assert_eq!(fill_license("No placeholders", 2024, ""), "No placeholders");
+    }
+
+    #[test]
+    fn gitea_template_fields() {
+        let request = CreateRequest {
+            name: "project".into(),
+            gitignores: "Rust,Node".into(),
+            license: "MIT".into(),
+            ..CreateRequest::default()
+        };
+
+        let local = LocalFiles::select(GitMode::Push, true, &request);
+        let files = render(&Templates, &request, "Jane Doe", local).unwrap();
+        assert_eq!(names(&files), vec!["README.md", "LICENSE", ".gitignore"]);
+        assert!(files[1].1.starts_with("MIT "));
+        assert!(files[1].1.ends_with(" Jane Doe\n"));
+        assert_eq!(files[2].1, "# Rust\n*.tmp\n\n# Node\n*.tmp\n");
+
+        let local = LocalFiles::select(GitMode::Init, false, &request);
+        let files = render(&Templates, &request, "Jane Doe", local).unwrap();
+        assert_eq!(names(&files), vec!["README.md", "LICENSE", ".gitignore"]);
+    }
+
+    const TEMPLATE: &str = "# Build output\ntarget\n/target\n\n\nCargo.lock\nCargo.lock\n\n";
+
+    #[test]
+    fn merge_skips_duplicate_lines() {
+        let merged = merge_gitignore("target\n*.log\n", TEMPLATE, "Rust").unwrap();
+        assert_eq!(merged,
+                   "target\n*.log\n\n# BEGIN gitignore template: Rust\n# Build output\n\
+                    /target\n\nCargo.lock\n# END gitignore template: Rust\n");
+
+        assert_eq!(merge_gitignore("/target\nCargo.lock\ntarget\n", TEMPLATE, "Rust"), None);
+    }
+
+    #[test]
+    fn merge_is_idempotent() {
+        let merged = merge_gitignore("", TEMPLATE, "Rust").unwrap();
+        assert!(merged.starts_with("# BEGIN gitignore template: Rust\n"));
+        assert_eq!(merge_gitignore(&merged, TEMPLATE, "Rust"), None);
+        assert_eq!(merge_gitignore(&merged, "*.pyc\n", "Rust"), None);
+        assert!(merge_gitignore(&merged, "*.pyc\n", "Python").is_some());
+    }
+
+    #[test]
+    fn merge_without_trailing_newline() {
+        let merged = merge_gitignore("*.log", "*.tmp", "Temp").unwrap();
+        assert_eq!(merged,
+                   "*.log\n\n# BEGIN gitignore template: Temp\n*.tmp\n\
+                    # END gitignore template: Temp\n");
+    }
+
+    #[test]
+    fn diff_appended_lines() {
+        let diff = diff(".gitignore", "1\n2\n3\n4\n5\n6\n", "1\n2\n3\n4\n5\n6\n7\n8\n");
+        assert_eq!(diff,
+                   "--- a/.gitignore\n+++ b/.gitignore\n@@ -4,3 +4,5 @@\n 4\n 5\n 6\n+7\n+8\n");
+    }
+
+    #[test]
+    fn diff_changed_line_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+        assert_eq!(diff("f", old, new),
+                   "--- a/f\n+++ b/f\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n");
+    }
+
+    #[test]
+    fn diff_empty_file() {
+        assert_eq!(diff("f", "", "a\nb\n"), "--- a/f\n+++ b/f\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+        assert_eq!(diff("f", "a\n", "x\na\n"), "--- a/f\n+++ b/f\n@@ -1,1 +1,2 @@\n+x\n a\n");
+    }
+}
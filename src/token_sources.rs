@@ -0,0 +1,207 @@
+use forge::ForgeKind;
+use git;
+use secret::Secret;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+fn read_file(path: PathBuf) -> Option<String> {
+    let mut text = String::new();
+    File::open(path).and_then(|mut file| file.read_to_string(&mut text)).ok().map(|_| text)
+}
+
+// Only an exact machine entry counts; the default entry is meant for
+// anonymous logins and would hand the same password to every forge
+pub fn parse_netrc(text: &str, host: &str) -> Option<Secret> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut words = lines.iter()
+        .enumerate()
+        .flat_map(|(n, line)| line.split_whitespace().map(move |word| (n, word)))
+        .peekable();
+    let mut matched = false;
+    while let Some((line, word)) = words.next() {
+        match word {
+            "machine" => {
+                matched = words.next().map(|(_, x)| x) == Some(host);
+            }
+            "default" => {
+                matched = false;
+            }
+            "password" => {
+                let password = words.next().map(|(_, x)| Secret::new(x));
+                if matched {
+                    return password;
+                }
+            }
+            "login" | "account" => {
+                words.next();
+            }
+            "macdef" => {
+                // The macro body runs up to the next empty line
+                let end = lines[line + 1..]
+                    .iter()
+                    .position(|x| x.trim().is_empty())
+                    .map_or(lines.len(), |i| line + 1 + i);
+                while words.peek().is_some_and(|&(n, _)| n < end) {
+                    words.next();
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+pub fn parse_gh_hosts(text: &str, host: &str) -> Option<Secret> {
+    let mut matched = false;
+    let mut host_indent = None;
+    let mut in_users = false;
+    let mut active = None;
+    let mut user = None;
+    let mut user_tokens = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        if indent == 0 {
+            matched = unquote(trimmed.trim_end_matches(':')) == host;
+            host_indent = None;
+            continue;
+        }
+        if !matched {
+            continue;
+        }
+
+        let mut parts = trimmed.splitn(2, ':');
+        let key = unquote(parts.next().unwrap_or(""));
+        let value = unquote(parts.next().unwrap_or(""));
+        if indent <= *host_indent.get_or_insert(indent) {
+            in_users = key == "users";
+            match key {
+                "oauth_token" if !value.is_empty() => return Some(Secret::new(value)),
+                "user" => active = Some(value),
+                _ => {}
+            }
+        } else if in_users && value.is_empty() {
+            user = Some(key);
+        } else if in_users && key == "oauth_token" {
+            if let Some(user) = user {
+                user_tokens.push((user, value));
+            }
+        }
+    }
+
+    let token = match active {
+        Some(active) => user_tokens.iter().find(|&&(user, _)| user == active),
+        None if user_tokens.len() == 1 => user_tokens.first(),
+        None => None,
+    };
+    token.map(|&(_, token)| Secret::new(token))
+}
+
+fn netrc_token(host: &str) -> Option<Secret> {
+    env::home_dir()
+        .and_then(|home| read_file(home.join(".netrc")))
+        .and_then(|text| parse_netrc(&text, host))
+}
+
+fn gh_hosts_path() -> Option<PathBuf> {
+    env::var_os("GH_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("XDG_CONFIG_HOME").map(|dir| PathBuf::from(dir).join("gh")))
+        .or_else(|| env::home_dir().map(|home| home.join(".config").join("gh")))
+        .map(|dir| dir.join("hosts.yml"))
+}
+
+fn gh_token(host: &str) -> Option<Secret> {
+    gh_hosts_path().and_then(read_file).and_then(|text| parse_gh_hosts(&text, host))
+}
+
+pub fn forge_token(forge: ForgeKind, host: &str) -> Option<Secret> {
+    let token = match forge {
+        ForgeKind::GitHub if host == "github.com" => {
+            netrc_token("api.github.com")
+                .or_else(|| netrc_token(host))
+                .map(|token| (token, "~/.netrc"))
+                .or_else(|| gh_token(host).map(|token| (token, "the gh CLI hosts file")))
+                .or_else(|| {
+                    git::get_config_value("github.token")
+                        .ok()
+                        .map(|token| (Secret::new(token), "git config github.token"))
+                })
+        }
+        ForgeKind::GitHub => {
+            netrc_token(host)
+                .map(|token| (token, "~/.netrc"))
+                .or_else(|| gh_token(host).map(|token| (token, "the gh CLI hosts file")))
+        }
+        ForgeKind::GitLab | ForgeKind::Gitea => netrc_token(host).map(|token| (token, "~/.netrc")),
+        ForgeKind::Local => None,
+    };
+
+    token.map(|(token, source)| {
+        info!("Using the token for {} from {}", host, source);
+        token
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secret::Secret;
+
+    #[test]
+    fn netrc_machine_and_default() {
+        let text = "default login anonymous password foo\n\
+                    machine gitlab.com login me password glpat-one\n\
+                    machine github.com\n  login me\n  password ghp_x\n";
+        assert_eq!(parse_netrc(text, "github.com"), Some(Secret::new("ghp_x")));
+        assert_eq!(parse_netrc(text, "gitlab.com"), Some(Secret::new("glpat-one")));
+        assert_eq!(parse_netrc(text, "api.github.com"), None);
+        assert_eq!(parse_netrc(text, "example.com"), None);
+        assert_eq!(parse_netrc("machine gitlab.com password x", "github.com"), None);
+    }
+
+    #[test]
+    fn netrc_skips_macro_bodies() {
+        let text = "macdef init\nmachine github.com password wrong\n\n\
+                    machine github.com login me password right\n";
+        assert_eq!(parse_netrc(text, "github.com"), Some(Secret::new("right")));
+
+        let text = "machine example.com login me macdef upload\n\
+                    machine github.com password wrong\n";
+        assert_eq!(parse_netrc(text, "github.com"), None);
+    }
+
+    #[test]
+    fn gh_hosts_single_account() {
+        let text = "github.com:\n    user: me\n    oauth_token: gho_token\n    \
+                    git_protocol: https\nghe.example.com:\n    oauth_token: \"gho_enterprise\"\n";
+        assert_eq!(parse_gh_hosts(text, "github.com"), Some(Secret::new("gho_token")));
+        assert_eq!(parse_gh_hosts(text, "ghe.example.com"),
+                   Some(Secret::new("gho_enterprise")));
+        assert_eq!(parse_gh_hosts(text, "gitlab.com"), None);
+    }
+
+    #[test]
+    fn gh_hosts_nested_users() {
+        let text = "github.com:\n    git_protocol: https\n    users:\n        me:\n            \
+                    oauth_token: gho_me\n        work:\n            oauth_token: gho_work\n    \
+                    user: work\n";
+        assert_eq!(parse_gh_hosts(text, "github.com"), Some(Secret::new("gho_work")));
+
+        let text = "github.com:\n    users:\n        me:\n            oauth_token: gho_me\n";
+        assert_eq!(parse_gh_hosts(text, "github.com"), Some(Secret::new("gho_me")));
+
+        let text = "github.com:\n    users:\n        me:\n        work:\n    user: work\n";
+        assert_eq!(parse_gh_hosts(text, "github.com"), None);
+    }
+}